@@ -0,0 +1,99 @@
+//! Compares element-wise `push`/`pop` with bulk `extend_from_slice`/`drain_into`
+//! on a `BufSocket`-sized ring, and measures `BufSocket` throughput over loopback.
+//!
+//! Run with `cargo bench --bench cyclicbuf`
+
+use std::{
+    hint::black_box,
+    io::Write,
+    net::TcpListener,
+    thread,
+    time::{Duration, Instant}
+};
+
+use mini_winclient::{
+    bufsocket::BufSocket,
+    socket::{Addr, LOCALHOST, ReadError},
+    utils::CyclicBuf
+};
+
+const CAPACITY: usize = 4096;
+const CHUNK_LEN: usize = 1024;
+const TOTAL_BYTES: usize = 64 * 1024 * 1024;
+
+fn report(name: &str, bytes: usize, elapsed: Duration) {
+    let mib_per_sec = bytes as f64 / (1024. * 1024.) / elapsed.as_secs_f64();
+    println!("{:<28} {:>10.2?} {:>10.1} MiB/s", name, elapsed, mib_per_sec);
+}
+
+fn bench_bytewise() -> Duration {
+    let mut buf: CyclicBuf<u8, CAPACITY> = CyclicBuf::default();
+    let chunk = [0x5a_u8; CHUNK_LEN];
+    let mut out = [0_u8; CHUNK_LEN];
+
+    let start = Instant::now();
+    for _ in 0..TOTAL_BYTES / CHUNK_LEN {
+        for b in black_box(&chunk) {
+            assert!(buf.push(*b));
+        }
+        for o in out.iter_mut() {
+            *o = buf.pop().unwrap();
+        }
+        black_box(&out);
+    }
+    start.elapsed()
+}
+
+fn bench_bulk() -> Duration {
+    let mut buf: CyclicBuf<u8, CAPACITY> = CyclicBuf::default();
+    let chunk = [0x5a_u8; CHUNK_LEN];
+    let mut out = [0_u8; CHUNK_LEN];
+
+    let start = Instant::now();
+    for _ in 0..TOTAL_BYTES / CHUNK_LEN {
+        assert_eq!(buf.extend_from_slice(black_box(&chunk)), CHUNK_LEN);
+        assert_eq!(buf.drain_into(&mut out), CHUNK_LEN);
+        black_box(&out);
+    }
+    start.elapsed()
+}
+
+/// Server floods 11 byte mouse move packages, client drains them through `BufSocket`
+fn bench_socket() -> Duration {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let package = [0, 0, 0, 7, 1, 4, 0, 10, 0, 20, 0];
+        let block: Vec<u8> = package.iter().cycle().take(package.len() * 4096).copied().collect();
+        let mut sent = 0;
+        while sent < TOTAL_BYTES {
+            stream.write_all(&block).unwrap();
+            sent += block.len();
+        }
+    });
+
+    let mut s: BufSocket<CAPACITY, CHUNK_LEN> = BufSocket::connect(Addr::v4(LOCALHOST, port)).unwrap();
+    let mut package = [0_u8; 11];
+    let mut received = 0;
+
+    let start = Instant::now();
+    while received < TOTAL_BYTES {
+        match s.bufferize_chunk() {
+            Ok(_) => {},
+            Err(ReadError::Eof) => break,
+            Err(err) => panic!("{:?}", err),
+        }
+        while s.bytes_available() >= package.len() {
+            received += s.read_bytes(&mut package);
+            black_box(&package);
+        }
+    }
+    start.elapsed()
+}
+
+fn main() {
+    report("CyclicBuf push/pop", TOTAL_BYTES, bench_bytewise());
+    report("CyclicBuf extend/drain", TOTAL_BYTES, bench_bulk());
+    report("BufSocket loopback", TOTAL_BYTES, bench_socket());
+}
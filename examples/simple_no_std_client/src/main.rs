@@ -14,13 +14,31 @@ use core::{
 
 use mini_winclient::{
     time::Point,
+    pixel::Gray8,
+    canvas::Canvas,
+    font::FONT_8X8,
     winclient::{
-        Format,
         Client,
+        PackageError,
+        PresentError,
+        Rect,
+        RegionError,
+    },
+    dump::{
+        dump,
+        DumpError,
+        DumpFormat,
+    },
+    write::{
+        FileFd,
+        WriteFd,
     },
-    write::WriteFd,
     STDOUT,
-    socket::LOCALHOST,
+    socket::{
+        Addr,
+        LOCALHOST,
+        WriteError,
+    },
 };
 
 #[cfg(target_arch = "x86")]
@@ -29,55 +47,6 @@ global_asm!(include_str!("x86/start.s"));
 #[cfg(target_arch = "x86_64")]
 global_asm!(include_str!("x86_64/start.s"));
 
-struct Canvas<'p> {
-    w: usize,
-    h: usize,
-    pixels: &'p mut [u8]
-}
-
-impl<'p> Canvas<'p> {
-    #[inline]
-    fn pixel_unchecked(&mut self, x: usize, y: usize) -> &mut u8 {
-        &mut self.pixels[x + y * self.w]
-    }
-
-    #[inline]
-    fn pixel(&mut self, x: isize, y: isize) -> Option<&mut u8> {
-        if x > 0 && y > 0 && x < self.w as isize && y < self.h as isize {
-            Some(self.pixel_unchecked(x as usize, y as usize))
-        } else {
-            None
-        }
-    }
-
-    fn draw_rect(&mut self, x0: isize, y0: isize, x1: isize, y1: isize, color: u8) {
-        let dx = x1 - x0;
-        let dy = y1 - y0;
-        if dx >= 0 {
-            for i in 0..=dx {
-                *self.pixel(x0 + i, y0).unwrap() = color;
-                *self.pixel(x0 + i, y0 + dy).unwrap() = color;
-            }
-        } else if dx < 0 {
-            for i in dx..0 {
-                *self.pixel(x0 + i, y0).unwrap() = color;
-                *self.pixel(x0 + i, y0 + dy).unwrap() = color;
-            }
-        }
-        if dy >= 0 {
-            for i in 0..=dy {
-                *self.pixel(x0, y0 + i).unwrap() = color;
-                *self.pixel(x0 + dx, y0 + i).unwrap() = color;
-            }
-        } else if dy < 0 {
-            for i in dy..0 {
-                *self.pixel(x0, y0 + i).unwrap() = color;
-                *self.pixel(x0 + dx, y0 + i).unwrap() = color;
-            }
-        }
-    }
-}
-
 #[no_mangle]
 pub extern "C" fn main(_argc: isize, _argv: *const *const u8) -> isize {
     use core::fmt::Write;
@@ -85,49 +54,107 @@ pub extern "C" fn main(_argc: isize, _argv: *const *const u8) -> isize {
     let mut stdout = WriteFd::new(STDOUT);
 
     writeln!(stdout, "start").unwrap();
-    let mut client: Client<256, 64> = Client::connect(LOCALHOST, 1234).unwrap();
+    let mut client: Client<256, 64> = Client::connect(Addr::v4(LOCALHOST, 1234)).unwrap();
 
     client.set_non_blocking_mode(true).unwrap();
 
 
     const W: usize = 1024;
     const H: usize = 1024;
-    let mut pixels: [u8; W * H] = [0; W * H];
+    let mut pixels = [Gray8(0); W * H];
+    {
+        let label = "mini_winclient\nno_std example";
+        let (w, h) = FONT_8X8.measure(label);
+        let mut canvas = Canvas::new(W as u16, H as u16, &mut pixels).unwrap();
+        canvas.text((W as i32 - w as i32) / 2, (H as i32 - h as i32) / 2, label, &FONT_8X8, Gray8(255), None);
+    }
 
     let mut i = 0;
     let mut j: usize = 0;
 
+    let mut full_frame_needed = true;
     let mut alive = true;
     while alive {
         writeln!(stdout, "try read:").unwrap();
-        while let Some(a) = client.read_package().unwrap() {
+        loop {
+            let a = match client.read_package() {
+                Ok(Some(a)) => a,
+                Ok(None) => break,
+                Err(PackageError::Disconnected) => {
+                    writeln!(stdout, "server disconnected").unwrap();
+                    return 0;
+                },
+                Err(err) => panic!("read failed: {:?}", err),
+            };
             match a {
-                mini_winclient::winclient::Package::Init(_) => todo!(),
+                mini_winclient::winclient::Package::Init(init) => writeln!(stdout, "reinit: {:?}", init).unwrap(),
                 mini_winclient::winclient::Package::Event(event) => match event {
                     mini_winclient::event::Event::Close => alive = false,
                     mini_winclient::event::Event::Resize { w, h } => writeln!(stdout, "resize: {}x{}", w, h).unwrap(),
+                    mini_winclient::event::Event::MouseDown { x, y, button } => writeln!(stdout, "mouse down: {}, {} ({})", x, y, button).unwrap(),
+                    mini_winclient::event::Event::MouseUp { x, y, button } => writeln!(stdout, "mouse up: {}, {} ({})", x, y, button).unwrap(),
+                    mini_winclient::event::Event::MouseMove { x, y, buttons } => writeln!(stdout, "mouse move: {}, {} ({})", x, y, buttons).unwrap(),
+                    mini_winclient::event::Event::KeyDown(key) => {
+                        writeln!(stdout, "key down: {} ({:?})", key.key, key.text()).unwrap();
+                        // pixels still hold the last presented frame
+                        if key.text() == Some("s") {
+                            let result = FileFd::create(c"frame.pgm")
+                                .map_err(DumpError::WriteError)
+                                .and_then(|mut file| dump(&mut file, DumpFormat::Pnm, W as u16, H as u16, &pixels));
+                            writeln!(stdout, "screenshot: {:?}", result).unwrap();
+                        }
+                    },
+                    mini_winclient::event::Event::KeyUp(key) => writeln!(stdout, "key up: {}", key.key).unwrap(),
                 },
             }
         }
         writeln!(stdout, "end reading").unwrap();
 
 
-        pixels[i] = 255;
+        pixels[i] = Gray8(255);
+        let dot = Rect::new((i % W) as u16, (i / W) as u16, 1, 1);
         i += 1;
         j = (j + 2) % 100;
+        let (x0, y0) = (j + 3, j + 3);
+        let (x1, y1) = (W - j - 3, H - j - 3);
         {
-            let mut canvas = Canvas { w: W, h: H, pixels: &mut pixels };
-
-            canvas.draw_rect(
-                j as isize + 3,
-                j as isize + 3,
-                W as isize - j as isize - 3,
-                H as isize - j as isize - 3,
-                255
+            let mut canvas = Canvas::new(W as u16, H as u16, &mut pixels).unwrap();
+
+            canvas.rect(
+                x0 as i32,
+                y0 as i32,
+                (x1 - x0 + 1) as i32,
+                (y1 - y0 + 1) as i32,
+                Gray8(255)
             );
         }
 
-        client.present(Format::GS, W as u16, H as u16, &pixels).unwrap();
+        let result = if full_frame_needed {
+            client.present(W as u16, H as u16, &pixels).map_err(|err| match err {
+                PresentError::WriteError(err) => err,
+                err => panic!("bad frame: {:?}", err),
+            })
+        } else {
+            // only the dot and the outline changed
+            let rects = [
+                dot,
+                Rect::new(x0 as u16, y0 as u16, (x1 - x0 + 1) as u16, 1),
+                Rect::new(x0 as u16, y1 as u16, (x1 - x0 + 1) as u16, 1),
+                Rect::new(x0 as u16, y0 as u16 + 1, 1, (y1 - y0 - 1) as u16),
+                Rect::new(x1 as u16, y0 as u16 + 1, 1, (y1 - y0 - 1) as u16),
+            ];
+            client.present_region(W as u16, H as u16, &pixels, &rects).map_err(|err| match err {
+                RegionError::WriteError(err) => err,
+                err => panic!("bad region: {:?}", err),
+            })
+        };
+        match result {
+            Ok(()) => full_frame_needed = false,
+            // frame dropped or sent partially (next present finishes it),
+            // either way server misses part of this update
+            Err(WriteError::WouldBlock) => full_frame_needed = true,
+            Err(err) => panic!("present failed: {:?}", err),
+        }
 
         writeln!(stdout, "i: {}, (id: {})", i, client.id()).unwrap();
         Point::now().loop_for(Duration::from_millis(100));
@@ -182,3 +209,40 @@ fn memcpy(dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void
         dest
     }
 }
+
+#[no_mangle]
+fn memmove(dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void
+{
+    unsafe {
+        let dest_p = dest as *mut u8;
+        let src_p = src as *const u8;
+
+        // copy backwards when dest overlaps tail of src
+        if (dest_p as usize) < (src_p as usize) {
+            for i in 0..n {
+                *dest_p.add(i) = *src_p.add(i)
+            }
+        } else {
+            for i in (0..n).rev() {
+                *dest_p.add(i) = *src_p.add(i)
+            }
+        }
+        dest
+    }
+}
+
+#[no_mangle]
+fn memcmp(s1: *const c_void, s2: *const c_void, n: usize) -> c_int
+{
+    unsafe {
+        let s1 = slice::from_raw_parts(s1 as *const u8, n);
+        let s2 = slice::from_raw_parts(s2 as *const u8, n);
+
+        for i in 0..n {
+            if s1[i] != s2[i] {
+                return s1[i] as c_int - s2[i] as c_int
+            }
+        }
+        0
+    }
+}
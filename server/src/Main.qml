@@ -6,10 +6,12 @@ Item {
     id: root
 
     required property int port;
+    required property string socketPath;
 
     Instantiator {
         model: MiniWinServer {
             port: root.port
+            socketPath: root.socketPath
             verbose: true
         }
 
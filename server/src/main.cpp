@@ -3,20 +3,29 @@
 #include <QCommandLineParser>
 #include <QGuiApplication>
 #include <QQmlApplicationEngine>
+#include <QStandardPaths>
 
 struct Args
 {
     quint16 port;
+    QString socketPath;
 };
 
 namespace {
 std::optional<Args> parseArgs(const QStringList &args)
 {
     static const auto portOption = QCommandLineOption{{"p", "port"}, "Port of server", "PORT", 0};
+    static const auto socketOption
+        = QCommandLineOption{{"s", "socket"},
+                             "Unix socket of server, empty to disable",
+                             "PATH",
+                             QStandardPaths::writableLocation(QStandardPaths::RuntimeLocation)
+                                 + "/mini-winserver.sock"};
     static const auto helpOption = QCommandLineOption{{"h", "help"}};
 
     QCommandLineParser parser;
     parser.addOption(portOption);
+    parser.addOption(socketOption);
     parser.addOption(helpOption);
     parser.parse(args);
 
@@ -25,7 +34,8 @@ std::optional<Args> parseArgs(const QStringList &args)
         return std::nullopt;
     }
 
-    return Args{.port = parser.value(portOption).toUShort(nullptr)};
+    return Args{.port = parser.value(portOption).toUShort(nullptr),
+                .socketPath = parser.value(socketOption)};
 }
 } // namespace
 
@@ -34,7 +44,7 @@ int main(int argc, char *argv[])
     QGuiApplication app(argc, argv);
     if (const auto args = parseArgs(app.arguments())) {
         QQmlApplicationEngine engine;
-        engine.setInitialProperties({{"port", args->port}});
+        engine.setInitialProperties({{"port", args->port}, {"socketPath", args->socketPath}});
         const QUrl url(u"qrc:/org/burbokop/mini_winserver/Main.qml"_qs);
         QObject::connect(
             &engine,
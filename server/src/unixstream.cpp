@@ -0,0 +1,171 @@
+#include "unixstream.h"
+
+#include <QSocketNotifier>
+#include <cerrno>
+#include <cstring>
+#include <fcntl.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+namespace {
+
+constexpr qsizetype chunkSize = 64 * 1024;
+/// Descriptors accepted with one recvmsg, client passes one per package
+constexpr std::size_t maxFds = 4;
+
+} // namespace
+
+UnixStream::UnixStream(int fd, QObject *parent)
+    : QIODevice(parent)
+    , m_fd(fd)
+    , m_readNotifier(new QSocketNotifier(fd, QSocketNotifier::Read, this))
+    , m_writeNotifier(new QSocketNotifier(fd, QSocketNotifier::Write, this))
+{
+    ::fcntl(m_fd, F_SETFL, ::fcntl(m_fd, F_GETFL) | O_NONBLOCK);
+    m_writeNotifier->setEnabled(false);
+    connect(m_readNotifier, &QSocketNotifier::activated, this, &UnixStream::receive);
+    connect(m_writeNotifier, &QSocketNotifier::activated, this, &UnixStream::sendPending);
+    open(QIODevice::ReadWrite | QIODevice::Unbuffered);
+}
+
+UnixStream::~UnixStream()
+{
+    release();
+}
+
+qint64 UnixStream::bytesAvailable() const
+{
+    return m_in.size() + QIODevice::bytesAvailable();
+}
+
+void UnixStream::close()
+{
+    if (m_fd < 0)
+        return;
+
+    // best effort, e.g. init with version mismatch before disconnect
+    sendPending();
+    release();
+    QIODevice::close();
+    emit disconnected();
+}
+
+std::optional<int> UnixStream::takeFd()
+{
+    if (m_fds.empty())
+        return std::nullopt;
+
+    const int fd = m_fds.front();
+    m_fds.pop_front();
+    return fd;
+}
+
+qint64 UnixStream::readData(char *data, qint64 maxSize)
+{
+    const qsizetype len = std::min<qint64>(maxSize, m_in.size());
+    std::memcpy(data, m_in.constData(), len);
+    m_in.remove(0, len);
+    return len;
+}
+
+qint64 UnixStream::writeData(const char *data, qint64 maxSize)
+{
+    if (m_fd < 0)
+        return -1;
+
+    m_out.append(data, maxSize);
+    sendPending();
+    return maxSize;
+}
+
+void UnixStream::receive()
+{
+    bool received = false;
+    bool eof = false;
+    while (m_fd >= 0) {
+        const qsizetype oldSize = m_in.size();
+        m_in.resize(oldSize + chunkSize);
+        iovec iov{.iov_base = m_in.data() + oldSize, .iov_len = std::size_t(chunkSize)};
+        alignas(cmsghdr) char control[CMSG_SPACE(sizeof(int) * maxFds)];
+        msghdr msg{};
+        msg.msg_iov = &iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control;
+        msg.msg_controllen = sizeof(control);
+
+        const ssize_t n = ::recvmsg(m_fd, &msg, MSG_CMSG_CLOEXEC);
+        m_in.resize(oldSize + std::max<ssize_t>(n, 0));
+        if (n < 0) {
+            if (errno == EINTR)
+                continue;
+            if (errno != EAGAIN && errno != EWOULDBLOCK) {
+                setErrorString(qt_error_string(errno));
+                eof = true;
+            }
+            break;
+        }
+
+        for (cmsghdr *cmsg = CMSG_FIRSTHDR(&msg); cmsg; cmsg = CMSG_NXTHDR(&msg, cmsg)) {
+            if (cmsg->cmsg_level != SOL_SOCKET || cmsg->cmsg_type != SCM_RIGHTS)
+                continue;
+            const std::size_t count = (cmsg->cmsg_len - CMSG_LEN(0)) / sizeof(int);
+            for (std::size_t i = 0; i < count; ++i) {
+                int fd;
+                std::memcpy(&fd, CMSG_DATA(cmsg) + i * sizeof(int), sizeof(int));
+                m_fds.push_back(fd);
+            }
+        }
+
+        if (n == 0) {
+            eof = true;
+            break;
+        }
+        received = true;
+    }
+
+    // packages received before disconnect are still handled
+    if (received)
+        emit readyRead();
+    if (eof)
+        close();
+}
+
+void UnixStream::sendPending()
+{
+    while (m_fd >= 0 && !m_out.isEmpty()) {
+        const ssize_t n = ::send(m_fd, m_out.constData(), m_out.size(), MSG_NOSIGNAL);
+        if (n < 0) {
+            if (errno == EINTR)
+                continue;
+            if (errno != EAGAIN && errno != EWOULDBLOCK) {
+                setErrorString(qt_error_string(errno));
+                m_out.clear();
+                // not from inside of write() of caller
+                QMetaObject::invokeMethod(this, &UnixStream::close, Qt::QueuedConnection);
+            }
+            break;
+        }
+        m_out.remove(0, n);
+    }
+    if (m_fd >= 0)
+        m_writeNotifier->setEnabled(!m_out.isEmpty());
+}
+
+void UnixStream::release()
+{
+    if (m_fd < 0)
+        return;
+
+    m_readNotifier->setEnabled(false);
+    m_writeNotifier->setEnabled(false);
+    ::close(m_fd);
+    m_fd = -1;
+    for (const int fd : m_fds)
+        ::close(fd);
+    m_fds.clear();
+}
+
+void UnixServer::incomingConnection(quintptr socketDescriptor)
+{
+    emit newStream(new UnixStream(int(socketDescriptor), this));
+}
@@ -0,0 +1,61 @@
+#pragma once
+
+#include <QIODevice>
+#include <QLocalServer>
+#include <deque>
+#include <optional>
+
+class QSocketNotifier;
+
+/// Connected unix stream socket that also receives descriptors passed with SCM_RIGHTS.
+/// QLocalSocket reads with plain read(2), which drops passed descriptors
+class UnixStream : public QIODevice
+{
+    Q_OBJECT
+
+public:
+    /// Takes ownership of connected socket `fd`
+    explicit UnixStream(int fd, QObject *parent = nullptr);
+    ~UnixStream() override;
+
+    bool isSequential() const override { return true; }
+    qint64 bytesAvailable() const override;
+    void close() override;
+
+    /// Oldest received descriptor which is not taken yet, caller owns it
+    std::optional<int> takeFd();
+
+signals:
+    void disconnected();
+
+protected:
+    qint64 readData(char *data, qint64 maxSize) override;
+    qint64 writeData(const char *data, qint64 maxSize) override;
+
+private:
+    void receive();
+    void sendPending();
+    void release();
+
+    int m_fd = -1;
+    QSocketNotifier *m_readNotifier = nullptr;
+    QSocketNotifier *m_writeNotifier = nullptr;
+    QByteArray m_in;
+    QByteArray m_out;
+    std::deque<int> m_fds;
+};
+
+/// Local server handing out connections as `UnixStream`
+class UnixServer : public QLocalServer
+{
+    Q_OBJECT
+
+public:
+    using QLocalServer::QLocalServer;
+
+signals:
+    void newStream(UnixStream *stream);
+
+protected:
+    void incomingConnection(quintptr socketDescriptor) override;
+};
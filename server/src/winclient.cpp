@@ -3,7 +3,13 @@
 #include <QQmlInfo>
 #include <QTcpSocket>
 #include <bit>
+#include <cstring>
+#include <fcntl.h>
+#include <sys/mman.h>
+#include <sys/stat.h>
+#include <unistd.h>
 #include <vgapalette.h>
+#include "unixstream.h"
 
 #define WARN_WITH_MSG(cond, msg) \
     if (!(cond)) { \
@@ -13,9 +19,39 @@
 
 namespace {
 
+constexpr quint8 minProtoVersion = 1;
+constexpr quint8 maxProtoVersion = 3;
+/// First protocol version with compression field in present header
+constexpr quint8 compressionProtoVersion = 2;
+/// First protocol version with delta frames
+constexpr quint8 deltaProtoVersion = 3;
+
+namespace compression {
+constexpr quint8 None = 0;
+constexpr quint8 Rle = 1;
+constexpr quint8 Lz = 2;
+/// Skip/copy runs against previous frame
+constexpr quint8 Delta = 3;
+} // namespace compression
+
+namespace capabilities {
+constexpr quint32 MouseEvents = 1 << 0;
+constexpr quint32 KeyEvents = 1 << 1;
+/// Descriptors are passed only over unix socket
+constexpr quint32 ShmFrames = 1 << 2;
+constexpr quint32 RegionFrames = 1 << 3;
+constexpr quint32 Palette = 1 << 4;
+constexpr quint32 All = MouseEvents | KeyEvents | RegionFrames | Palette;
+} // namespace capabilities
+
 namespace in_package_types {
 constexpr quint8 Present = 0;
-}
+constexpr quint8 Hello = 1;
+constexpr quint8 ShmAttach = 2;
+constexpr quint8 ShmPresent = 3;
+constexpr quint8 PresentRegion = 4;
+constexpr quint8 SetPalette = 5;
+} // namespace in_package_types
 
 namespace out_package_types {
 constexpr quint8 Init = 0;
@@ -28,8 +64,57 @@ constexpr quint8 Resize = 1;
 constexpr quint8 MouseDown = 2;
 constexpr quint8 MouseUp = 3;
 constexpr quint8 MouseMove = 4;
+constexpr quint8 KeyDown = 5;
+constexpr quint8 KeyUp = 6;
 } // namespace event_types
 
+namespace mouse_buttons {
+constexpr quint8 Left = 1 << 0;
+constexpr quint8 Right = 1 << 1;
+constexpr quint8 Middle = 1 << 2;
+} // namespace mouse_buttons
+
+namespace modifiers {
+constexpr quint8 Shift = 1 << 0;
+constexpr quint8 Control = 1 << 1;
+constexpr quint8 Alt = 1 << 2;
+constexpr quint8 Meta = 1 << 3;
+constexpr quint8 Keypad = 1 << 4;
+} // namespace modifiers
+
+namespace keys {
+constexpr quint16 Unknown = 0;
+constexpr quint16 Escape = 0x100;
+constexpr quint16 Tab = 0x101;
+constexpr quint16 Backtab = 0x102;
+constexpr quint16 Backspace = 0x103;
+constexpr quint16 Return = 0x104;
+constexpr quint16 Enter = 0x105;
+constexpr quint16 Insert = 0x106;
+constexpr quint16 Delete = 0x107;
+constexpr quint16 Pause = 0x108;
+constexpr quint16 Print = 0x109;
+constexpr quint16 Home = 0x110;
+constexpr quint16 End = 0x111;
+constexpr quint16 Left = 0x112;
+constexpr quint16 Up = 0x113;
+constexpr quint16 Right = 0x114;
+constexpr quint16 Down = 0x115;
+constexpr quint16 PageUp = 0x116;
+constexpr quint16 PageDown = 0x117;
+constexpr quint16 Shift = 0x120;
+constexpr quint16 Control = 0x121;
+constexpr quint16 Meta = 0x122;
+constexpr quint16 Alt = 0x123;
+constexpr quint16 CapsLock = 0x124;
+constexpr quint16 NumLock = 0x125;
+constexpr quint16 ScrollLock = 0x126;
+constexpr quint16 F1 = 0x130;
+} // namespace keys
+
+/// Max bytes of utf-8 text in key event
+constexpr qsizetype keyTextCapacity = 16;
+
 namespace transmute {
 
 constexpr void to_be(char *p, std::size_t s)
@@ -79,6 +164,8 @@ public:
         return transmute::write(m_dev, v);
     }
 
+    std::size_t writeBytes(const QByteArray &bytes) { return m_dev->write(bytes); }
+
     friend void writePackage(QIODevice *dev, std::function<void(Package)> writeFn);
 
 private:
@@ -110,127 +197,735 @@ std::optional<QImage::Format> parseFormat(std::uint8_t fmt)
         return QImage::Format_ARGB32;
     case 2: // VGA
         return QImage::Format_Indexed8;
+    case 3:
+        return QImage::Format_RGB16;
+    case 4:
+        return QImage::Format_RGB888;
+    case 5:
+        return QImage::Format_RGBA8888;
+    case 6:
+        return QImage::Format_RGBA8888_Premultiplied;
+    case 7:
+        return QImage::Format_Mono;
     default:
         return std::nullopt;
     }
 }
 
-QImage createImage(const uchar *data, int width, int height, QImage::Format format)
+quint8 toWireButtons(Qt::MouseButtons buttons)
+{
+    quint8 result = 0;
+    if (buttons & Qt::LeftButton)
+        result |= mouse_buttons::Left;
+    if (buttons & Qt::RightButton)
+        result |= mouse_buttons::Right;
+    if (buttons & Qt::MiddleButton)
+        result |= mouse_buttons::Middle;
+    return result;
+}
+
+quint8 toWireModifiers(Qt::KeyboardModifiers mods)
+{
+    quint8 result = 0;
+    if (mods & Qt::ShiftModifier)
+        result |= modifiers::Shift;
+    if (mods & Qt::ControlModifier)
+        result |= modifiers::Control;
+    if (mods & Qt::AltModifier)
+        result |= modifiers::Alt;
+    if (mods & Qt::MetaModifier)
+        result |= modifiers::Meta;
+    if (mods & Qt::KeypadModifier)
+        result |= modifiers::Keypad;
+    return result;
+}
+
+quint16 toWireKey(int key)
+{
+    if (key >= Qt::Key_Space && key <= Qt::Key_AsciiTilde)
+        return quint16(key);
+    if (key >= Qt::Key_F1 && key <= Qt::Key_F12)
+        return quint16(keys::F1 + (key - Qt::Key_F1));
+
+    switch (key) {
+    case Qt::Key_Escape:
+        return keys::Escape;
+    case Qt::Key_Tab:
+        return keys::Tab;
+    case Qt::Key_Backtab:
+        return keys::Backtab;
+    case Qt::Key_Backspace:
+        return keys::Backspace;
+    case Qt::Key_Return:
+        return keys::Return;
+    case Qt::Key_Enter:
+        return keys::Enter;
+    case Qt::Key_Insert:
+        return keys::Insert;
+    case Qt::Key_Delete:
+        return keys::Delete;
+    case Qt::Key_Pause:
+        return keys::Pause;
+    case Qt::Key_Print:
+        return keys::Print;
+    case Qt::Key_Home:
+        return keys::Home;
+    case Qt::Key_End:
+        return keys::End;
+    case Qt::Key_Left:
+        return keys::Left;
+    case Qt::Key_Up:
+        return keys::Up;
+    case Qt::Key_Right:
+        return keys::Right;
+    case Qt::Key_Down:
+        return keys::Down;
+    case Qt::Key_PageUp:
+        return keys::PageUp;
+    case Qt::Key_PageDown:
+        return keys::PageDown;
+    case Qt::Key_Shift:
+        return keys::Shift;
+    case Qt::Key_Control:
+        return keys::Control;
+    case Qt::Key_Meta:
+        return keys::Meta;
+    case Qt::Key_Alt:
+        return keys::Alt;
+    case Qt::Key_CapsLock:
+        return keys::CapsLock;
+    case Qt::Key_NumLock:
+        return keys::NumLock;
+    case Qt::Key_ScrollLock:
+        return keys::ScrollLock;
+    default:
+        return keys::Unknown;
+    }
+}
+
+/// Utf-8 encoded text truncated to `keyTextCapacity` bytes on a char boundary
+QByteArray toWireKeyText(const QString &text)
+{
+    QByteArray result;
+    for (const auto &ch : text.toUcs4()) {
+        const auto encoded = QString::fromUcs4(&ch, 1).toUtf8();
+        if (result.size() + encoded.size() > keyTextCapacity)
+            break;
+        result += encoded;
+    }
+    return result;
+}
+
+/// PackBits style: control byte n < 128 is followed by n + 1 literals,
+/// n >= 128 by single byte repeated n - 125 times
+std::optional<QByteArray> rleDecode(const QByteArray &src, qsizetype expectedSize)
+{
+    QByteArray result;
+    result.reserve(expectedSize);
+    qsizetype i = 0;
+    while (i < src.size()) {
+        const auto n = quint8(src[i]);
+        if (n < 128) {
+            if (i + 2 + n > src.size())
+                return std::nullopt;
+            result.append(src.constData() + i + 1, n + 1);
+            i += 2 + n;
+        } else {
+            if (i + 1 >= src.size())
+                return std::nullopt;
+            result.append(n - 125, src[i + 1]);
+            i += 2;
+        }
+        if (result.size() > expectedSize)
+            return std::nullopt;
+    }
+    return result;
+}
+
+std::optional<qsizetype> lzReadLen(const QByteArray &src, qsizetype &i, qsizetype len)
+{
+    if (len == 15) {
+        quint8 b = 0;
+        do {
+            if (i >= src.size())
+                return std::nullopt;
+            b = quint8(src[i++]);
+            len += b;
+        } while (b == 255);
+    }
+    return len;
+}
+
+/// LZ4 block format
+std::optional<QByteArray> lzDecode(const QByteArray &src, qsizetype expectedSize)
+{
+    constexpr qsizetype minMatch = 4;
+
+    QByteArray result;
+    result.reserve(expectedSize);
+    qsizetype i = 0;
+    while (i < src.size()) {
+        const auto token = quint8(src[i++]);
+
+        const auto literals = lzReadLen(src, i, token >> 4);
+        if (!literals || i + *literals > src.size())
+            return std::nullopt;
+        result.append(src.constData() + i, *literals);
+        i += *literals;
+        if (i == src.size())
+            break;
+
+        if (i + 2 > src.size())
+            return std::nullopt;
+        const qsizetype offset = quint8(src[i]) | (qsizetype(quint8(src[i + 1])) << 8);
+        i += 2;
+        const auto len = lzReadLen(src, i, token & 0xf);
+        if (!len || offset == 0 || offset > result.size()
+            || result.size() + *len + minMatch > expectedSize)
+            return std::nullopt;
+        // match may overlap bytes it produces
+        for (qsizetype j = 0; j < *len + minMatch; ++j) {
+            result.append(result[result.size() - offset]);
+        }
+    }
+    return result;
+}
+
+std::optional<QByteArray> decompress(quint8 method, QByteArray &&src, qsizetype expectedSize)
+{
+    switch (method) {
+    case compression::None:
+        return std::move(src);
+    case compression::Rle:
+        return rleDecode(src, expectedSize);
+    case compression::Lz:
+        return lzDecode(src, expectedSize);
+    default:
+        return std::nullopt;
+    }
+}
+
+std::optional<qsizetype> readVarint(const QByteArray &src, qsizetype &i)
+{
+    qsizetype result = 0;
+    for (int shift = 0; i < src.size() && shift < 63; shift += 7) {
+        const auto b = quint8(src[i++]);
+        result |= qsizetype(b & 0x7f) << shift;
+        if (!(b & 0x80))
+            return result;
+    }
+    return std::nullopt;
+}
+
+/// Varint skip count, varint copy count and copied bytes until end of `src`
+std::optional<QByteArray> deltaApply(const QByteArray &src, QByteArray &&frame)
 {
-    auto result = QImage(data, width, height, format);
-    if (format == QImage::Format_Indexed8) {
-        result.setColorTable(QList<QRgb>(vga::palette, vga::palette + vga::paletteLen));
+    qsizetype i = 0;
+    qsizetype pos = 0;
+    while (i < src.size()) {
+        const auto skip = readVarint(src, i);
+        const auto count = readVarint(src, i);
+        if (!skip || !count || *skip > frame.size() - pos || *count > frame.size() - pos - *skip
+            || *count > src.size() - i)
+            return std::nullopt;
+        pos += *skip;
+        std::memcpy(frame.data() + pos, src.constData() + i, *count);
+        i += *count;
+        pos += *count;
     }
+    return std::move(frame);
+}
+
+/// Bytes of one row of `width` pixels, packed rows are padded to whole byte
+qsizetype rowBytes(QImage::Format format, int width)
+{
+    return (qsizetype(width) * QImage::toPixelFormat(format).bitsPerPixel() + 7) / 8;
+}
+
+/// Expected pixel size field of present header (bytes per element, 1 for packed formats)
+int elementSize(QImage::Format format)
+{
+    return (QImage::toPixelFormat(format).bitsPerPixel() + 7) / 8;
+}
+
+/// Swap 16 bit pixels between little endian wire order and host order (no-op on little endian hosts)
+void convertWireOrder(QImage::Format format, char *data, qsizetype len)
+{
+    if constexpr (std::endian::native == std::endian::big) {
+        if (format == QImage::Format_RGB16) {
+            for (qsizetype i = 0; i + 1 < len; i += 2) {
+                std::swap(data[i], data[i + 1]);
+            }
+        }
+    }
+}
+
+/// Pixels of `image` in wire order without scan line padding
+QByteArray imageBytes(const QImage &image)
+{
+    const qsizetype rowLen = rowBytes(image.format(), image.width());
+    QByteArray result;
+    result.reserve(rowLen * image.height());
+    for (int y = 0; y < image.height(); ++y) {
+        result.append(reinterpret_cast<const char *>(image.constScanLine(y)), rowLen);
+    }
+    convertWireOrder(image.format(), result.data(), result.size());
+    return result;
+}
+
+void applyColorTable(QImage &image)
+{
+    if (image.format() == QImage::Format_Indexed8) {
+        image.setColorTable(QList<QRgb>(vga::palette, vga::palette + vga::paletteLen));
+    } else if (image.format() == QImage::Format_Mono) {
+        image.setColorTable({qRgb(0, 0, 0), qRgb(255, 255, 255)});
+    }
+}
+
+QImage createImage(const uchar *data, int width, int height, QImage::Format format)
+{
+    auto result = QImage(data, width, height, rowBytes(format, width), format);
+    applyColorTable(result);
     return result;
 }
 
 } // namespace
 
-std::optional<WinClient::Frame> WinClient::parseFrame(QByteArray &&arr,
-                                                      std::uint8_t cid,
-                                                      QObject *giveMeErr)
+std::optional<WinClient::Hello> WinClient::parseHello(QIODevice *dev, QObject *giveMeErr)
 {
-    QBuffer buf(&arr);
-    buf.open(QIODevice::ReadOnly);
+    const auto minVersion = transmute::read<std::uint8_t>(dev);
+    WARN_WITH_MSG(minVersion, "Hello is too short");
+    const auto maxVersion = transmute::read<std::uint8_t>(dev);
+    WARN_WITH_MSG(maxVersion, "Hello is too short");
+    const auto caps = transmute::read<std::uint32_t>(dev);
+    WARN_WITH_MSG(caps, "Hello is too short");
+    WARN_WITH_MSG(*minVersion <= *maxVersion, "Invalid protocol version range");
 
-    const auto protoVersion = transmute::read<std::uint8_t>(&buf);
-    assert(protoVersion);
-    const auto clientId = transmute::read<std::uint8_t>(&buf);
-    assert(clientId);
-    WARN_WITH_MSG(*clientId == cid, "Client id does not match");
-    const auto packageType = transmute::read<std::uint8_t>(&buf);
-    assert(packageType);
-    WARN_WITH_MSG(*packageType == in_package_types::Present,
-                  "Server suports only package type " << in_package_types::Present << " (present)");
-    const auto format = transmute::read<std::uint8_t>(&buf);
+    return Hello{.minVersion = *minVersion, .maxVersion = *maxVersion, .capabilities = *caps};
+}
+
+std::optional<WinClient::Frame> WinClient::parseFrame(QIODevice *dev,
+                                                      std::uint8_t protoVersion,
+                                                      const std::optional<Frame> &prev,
+                                                      QObject *giveMeErr)
+{
+    const auto format = transmute::read<std::uint8_t>(dev);
     assert(format);
-    const auto pixelSize = transmute::read<std::uint8_t>(&buf);
+    const auto pixelSize = transmute::read<std::uint8_t>(dev);
     assert(pixelSize);
-    const auto w = transmute::read<std::uint16_t>(&buf);
+    const auto w = transmute::read<std::uint16_t>(dev);
     assert(w);
-    const auto h = transmute::read<std::uint16_t>(&buf);
+    const auto h = transmute::read<std::uint16_t>(dev);
     assert(h);
-    QByteArray pixels = buf.readAll();
-
-    WARN_WITH_MSG(pixels.size() == *w * *h * *pixelSize,
-                  "Parsing request failed: invalid pix array size");
-    WARN_WITH_MSG(*protoVersion == 0, "Server supports only protocol version 0");
-
+    std::optional<std::uint8_t> method = compression::None;
+    if (protoVersion >= compressionProtoVersion) {
+        method = transmute::read<std::uint8_t>(dev);
+        WARN_WITH_MSG(method, "Present header is too short");
+    }
     const auto fmt = parseFormat(*format);
     WARN_WITH_MSG(fmt, "Unknown pix format: " << *format);
+    WARN_WITH_MSG(elementSize(*fmt) == *pixelSize, "Pixel size does not match format");
+
+    const qsizetype expectedSize = rowBytes(*fmt, *w) * *h;
+    std::optional<QByteArray> pixels;
+    if (*method == compression::Delta) {
+        WARN_WITH_MSG(protoVersion >= deltaProtoVersion, "Delta frame in protocol " << protoVersion);
+        WARN_WITH_MSG(prev && prev->format == *format && prev->pixelSize == *pixelSize
+                          && prev->w == *w && prev->h == *h,
+                      "Delta frame without previous frame of the same layout");
+        pixels = deltaApply(dev->readAll(), imageBytes(prev->image));
+    } else {
+        pixels = decompress(*method, dev->readAll(), expectedSize);
+    }
+    WARN_WITH_MSG(pixels, "Parsing request failed: can not decompress pixels (method " << *method << ")");
+
+    WARN_WITH_MSG(pixels->size() == expectedSize,
+                  "Parsing request failed: invalid pix array size");
+    convertWireOrder(*fmt, pixels->data(), pixels->size());
 
-    const QImage image = createImage(reinterpret_cast<const uchar *>(pixels.constData()),
+    const QImage image = createImage(reinterpret_cast<const uchar *>(pixels->constData()),
                                      *w,
                                      *h,
                                      *fmt);
 
     WARN_WITH_MSG(!image.isNull(), "Invalid image");
-    WARN_WITH_MSG(image.depth() == (*pixelSize * 8),
-                  "Image depth not match with received pixel size");
 
-    return Frame{.protoVersion = *protoVersion,
-                 .format = *protoVersion,
+    return Frame{.protoVersion = protoVersion,
+                 .format = *format,
                  .pixelSize = *pixelSize,
                  .w = *w,
                  .h = *h,
                  .image = image.copy()};
 }
 
+std::optional<WinClient::Frame> WinClient::parseRegion(QIODevice *dev,
+                                                       std::uint8_t protoVersion,
+                                                       const std::optional<Frame> &prev,
+                                                       QObject *giveMeErr)
+{
+    const auto format = transmute::read<std::uint8_t>(dev);
+    const auto pixelSize = transmute::read<std::uint8_t>(dev);
+    const auto w = transmute::read<std::uint16_t>(dev);
+    const auto h = transmute::read<std::uint16_t>(dev);
+    const auto rectCount = transmute::read<std::uint8_t>(dev);
+    WARN_WITH_MSG(format && pixelSize && w && h && rectCount, "Region header is too short");
+
+    const auto fmt = parseFormat(*format);
+    WARN_WITH_MSG(fmt, "Unknown pix format: " << *format);
+    WARN_WITH_MSG(elementSize(*fmt) == *pixelSize, "Pixel size does not match format");
+    const int bits = QImage::toPixelFormat(*fmt).bitsPerPixel();
+
+    QList<QRect> rects;
+    for (std::uint8_t i = 0; i < *rectCount; ++i) {
+        const auto x = transmute::read<std::uint16_t>(dev);
+        const auto y = transmute::read<std::uint16_t>(dev);
+        const auto rw = transmute::read<std::uint16_t>(dev);
+        const auto rh = transmute::read<std::uint16_t>(dev);
+        WARN_WITH_MSG(x && y && rw && rh, "Region rects are too short");
+        WARN_WITH_MSG(*x + *rw <= *w && *y + *rh <= *h, "Region rect is outside of surface");
+        WARN_WITH_MSG(*x * bits % 8 == 0, "Region rect does not start at byte boundary");
+        rects.push_back(QRect(*x, *y, *rw, *rh));
+    }
+
+    // pixels outside of rects are kept from previous frame of the same layout
+    QImage image;
+    if (prev && prev->format == *format && prev->pixelSize == *pixelSize && prev->w == *w
+        && prev->h == *h) {
+        image = prev->image;
+    } else {
+        image = QImage(*w, *h, *fmt);
+        applyColorTable(image);
+        image.fill(0);
+    }
+
+    WARN_WITH_MSG(!image.isNull(), "Invalid image");
+
+    for (const auto &rect : rects) {
+        const qsizetype rowLen = rowBytes(*fmt, rect.width());
+        for (int y = rect.top(); y <= rect.bottom(); ++y) {
+            QByteArray row = dev->read(rowLen);
+            WARN_WITH_MSG(row.size() == rowLen, "Parsing request failed: invalid pix array size");
+            convertWireOrder(*fmt, row.data(), row.size());
+            std::memcpy(image.scanLine(y) + rect.x() * bits / 8, row.constData(), rowLen);
+        }
+    }
+    WARN_WITH_MSG(dev->atEnd(), "Parsing request failed: invalid pix array size");
+
+    return Frame{.protoVersion = protoVersion,
+                 .format = *format,
+                 .pixelSize = *pixelSize,
+                 .w = *w,
+                 .h = *h,
+                 .image = image};
+}
+
+std::optional<WinClient::PaletteUpdate> WinClient::parsePalette(QIODevice *dev, QObject *giveMeErr)
+{
+    const auto first = transmute::read<std::uint8_t>(dev);
+    const auto count = transmute::read<std::uint16_t>(dev);
+    WARN_WITH_MSG(first && count, "Palette header is too short");
+    WARN_WITH_MSG(*count > 0 && *first + *count <= vga::paletteLen, "Palette range is out of bounds");
+
+    QList<QRgb> colors;
+    for (std::uint16_t i = 0; i < *count; ++i) {
+        const QByteArray rgb = dev->read(3);
+        WARN_WITH_MSG(rgb.size() == 3, "Palette colors are too short");
+        colors.push_back(qRgb(quint8(rgb[0]), quint8(rgb[1]), quint8(rgb[2])));
+    }
+    WARN_WITH_MSG(dev->atEnd(), "Palette has trailing bytes");
+
+    return PaletteUpdate{.first = *first, .colors = colors};
+}
+
+std::optional<WinClient::ShmBuffer> WinClient::parseShmAttach(QIODevice *dev, int fd, QObject *giveMeErr)
+{
+    const auto format = transmute::read<std::uint8_t>(dev);
+    const auto pixelSize = transmute::read<std::uint8_t>(dev);
+    const auto w = transmute::read<std::uint16_t>(dev);
+    const auto h = transmute::read<std::uint16_t>(dev);
+    WARN_WITH_MSG(format && pixelSize && w && h, "Shared memory attach is too short");
+
+    const auto fmt = parseFormat(*format);
+    WARN_WITH_MSG(fmt, "Unknown pix format: " << *format);
+    WARN_WITH_MSG(elementSize(*fmt) == *pixelSize, "Pixel size does not match format");
+
+    const qsizetype len = rowBytes(*fmt, *w) * *h;
+    struct stat st;
+    WARN_WITH_MSG(len > 0 && ::fstat(fd, &st) == 0 && st.st_size >= len,
+                  "Shared memory is smaller than surface");
+    // otherwise client could shrink it and crash server with SIGBUS
+    const int seals = ::fcntl(fd, F_GET_SEALS);
+    WARN_WITH_MSG(seals >= 0 && (seals & F_SEAL_SHRINK), "Shared memory is not sealed against shrinking");
+
+    void *data = ::mmap(nullptr, len, PROT_READ, MAP_SHARED, fd, 0);
+    WARN_WITH_MSG(data != MAP_FAILED, "Can not map shared memory: " << qt_error_string(errno));
+
+    return ShmBuffer{.format = *format,
+                     .pixelSize = *pixelSize,
+                     .w = *w,
+                     .h = *h,
+                     .data = static_cast<const uchar *>(data),
+                     .len = len};
+}
+
+std::optional<QRect> WinClient::parseShmPresent(QIODevice *dev, const ShmBuffer &shm, QObject *giveMeErr)
+{
+    const auto x = transmute::read<std::uint16_t>(dev);
+    const auto y = transmute::read<std::uint16_t>(dev);
+    const auto w = transmute::read<std::uint16_t>(dev);
+    const auto h = transmute::read<std::uint16_t>(dev);
+    WARN_WITH_MSG(x && y && w && h, "Shared memory damage is too short");
+    WARN_WITH_MSG(*x + *w <= shm.w && *y + *h <= shm.h, "Damage is outside of shared memory");
+    WARN_WITH_MSG(dev->atEnd(), "Shared memory damage has trailing bytes");
+
+    return QRect(*x, *y, *w, *h);
+}
+
+WinClient::Frame WinClient::shmFrame(const QRect &damage) const
+{
+    const auto fmt = *parseFormat(m_shm->format);
+    const int bits = QImage::toPixelFormat(fmt).bitsPerPixel();
+
+    // pixels outside of damage are kept from previous frame of the same layout
+    QImage image;
+    if (m_frame && m_frame->format == m_shm->format && m_frame->pixelSize == m_shm->pixelSize
+        && m_frame->w == m_shm->w && m_frame->h == m_shm->h) {
+        image = m_frame->image;
+    } else {
+        image = QImage(m_shm->w, m_shm->h, fmt);
+        applyColorTable(image);
+        image.fill(0);
+    }
+
+    // packed rows are copied in whole bytes, memory is in host order unlike wire
+    const qsizetype stride = rowBytes(fmt, m_shm->w);
+    const qsizetype first = qsizetype(damage.x()) * bits / 8;
+    const qsizetype last = (qsizetype(damage.x() + damage.width()) * bits + 7) / 8;
+    for (int y = damage.top(); y <= damage.bottom(); ++y) {
+        std::memcpy(image.scanLine(y) + first, m_shm->data + y * stride + first, last - first);
+    }
+
+    return Frame{.protoVersion = *m_protoVersion,
+                 .format = m_shm->format,
+                 .pixelSize = m_shm->pixelSize,
+                 .w = m_shm->w,
+                 .h = m_shm->h,
+                 .image = image};
+}
+
+void WinClient::detachShm()
+{
+    if (m_shm) {
+        ::munmap(const_cast<uchar *>(m_shm->data), m_shm->len);
+        m_shm = std::nullopt;
+    }
+}
+
+void WinClient::setPalette(const PaletteUpdate &update)
+{
+    std::copy(update.colors.begin(), update.colors.end(), m_palette.begin() + update.first);
+    if (m_frame && m_frame->image.format() == QImage::Format_Indexed8) {
+        m_frame->image.setColorTable(m_palette);
+        emit imageChanged();
+    }
+}
+
+void WinClient::setFrame(Frame &&frame)
+{
+    m_frame = std::move(frame);
+    // frames are parsed with default palette
+    if (m_frame->image.format() == QImage::Format_Indexed8) {
+        m_frame->image.setColorTable(m_palette);
+    }
+    if (width() != m_prevW) {
+        emit widthChanged();
+        m_prevW = width();
+    }
+    if (height() != m_prevH) {
+        emit heightChanged();
+        m_prevH = height();
+    }
+    emit imageChanged();
+    emit titleChanged();
+}
+
+void WinClient::handlePackage(QByteArray &&arr)
+{
+    QBuffer buf(&arr);
+    buf.open(QIODevice::ReadOnly);
+
+    const auto protoVersion = transmute::read<std::uint8_t>(&buf);
+    const auto clientId = transmute::read<std::uint8_t>(&buf);
+    const auto packageType = transmute::read<std::uint8_t>(&buf);
+    if (!protoVersion || !clientId || !packageType) {
+        qmlWarning(this) << "Package header is too short";
+        return;
+    }
+
+    if (*packageType == in_package_types::Hello) {
+        if (const auto hello = parseHello(&buf, this)) {
+            handleHello(*hello);
+        }
+        return;
+    }
+
+    if (!m_protoVersion) {
+        qmlWarning(this) << "Package " << *packageType << " received before hello";
+        return;
+    }
+    if (*protoVersion != *m_protoVersion) {
+        qmlWarning(this) << "Protocol version " << *protoVersion
+                         << " does not match negotiated " << *m_protoVersion;
+        return;
+    }
+    if (*clientId != m_id) {
+        qmlWarning(this) << "Client id does not match";
+        return;
+    }
+
+    switch (*packageType) {
+    case in_package_types::Present:
+        if (auto frame = parseFrame(&buf, *protoVersion, m_frame, this)) {
+            setFrame(std::move(*frame));
+        }
+        break;
+    case in_package_types::PresentRegion:
+        if (auto frame = parseRegion(&buf, *protoVersion, m_frame, this)) {
+            setFrame(std::move(*frame));
+        }
+        break;
+    case in_package_types::ShmAttach: {
+        // descriptor comes with first bytes of package, so it is queued by now
+        const auto fd = m_unixStream ? m_unixStream->takeFd() : std::nullopt;
+        if (!(m_capabilities & capabilities::ShmFrames)) {
+            qmlWarning(this) << "Shared memory attach received but not enabled";
+        } else if (!fd) {
+            qmlWarning(this) << "Shared memory attach without descriptor";
+        } else if (const auto shm = parseShmAttach(&buf, *fd, this)) {
+            detachShm();
+            m_shm = *shm;
+        }
+        // mapping stays valid without descriptor
+        if (fd) {
+            ::close(*fd);
+        }
+        break;
+    }
+    case in_package_types::ShmPresent:
+        if (!m_shm) {
+            qmlWarning(this) << "Shared memory present before attach";
+        } else if (const auto damage = parseShmPresent(&buf, *m_shm, this)) {
+            setFrame(shmFrame(*damage));
+        }
+        break;
+    case in_package_types::SetPalette:
+        if (!(m_capabilities & capabilities::Palette)) {
+            qmlWarning(this) << "Palette received but not enabled";
+        } else if (const auto update = parsePalette(&buf, this)) {
+            setPalette(*update);
+        }
+        break;
+    default:
+        qmlWarning(this) << "Unknown package type: " << *packageType;
+    }
+}
+
+void WinClient::handleHello(const Hello &hello)
+{
+    const auto version = std::min(hello.maxVersion, maxProtoVersion);
+    const bool versionOk = version >= std::max(hello.minVersion, minProtoVersion);
+
+    const quint32 supported = m_unixStream ? capabilities::All | capabilities::ShmFrames
+                                           : capabilities::All;
+    m_capabilities = versionOk ? hello.capabilities & supported : 0;
+    detachShm();
+    writePackage(m_socket, [this, version, versionOk](Package p) {
+        p.write(out_package_types::Init);
+        p.write(m_id);
+        // on mismatch client sees server's own version and gives up
+        p.write(versionOk ? version : maxProtoVersion);
+        p.write(m_capabilities);
+        p.write(quint16(width()));
+        p.write(quint16(height()));
+    });
+
+    if (versionOk) {
+        m_protoVersion = version;
+    } else {
+        qmlWarning(this) << "Client protocol versions " << hello.minVersion << ".."
+                         << hello.maxVersion << " are not supported";
+        m_protoVersion = std::nullopt;
+        if (const auto tcp = qobject_cast<QTcpSocket *>(m_socket)) {
+            tcp->disconnectFromHost();
+        } else {
+            m_socket->close();
+        }
+    }
+}
+
 WinClient::WinClient(QTcpSocket *socket, uint8_t clientId, QObject *parent)
     : QObject(parent)
     , m_socket(socket)
     , m_id(clientId)
+    , m_palette(vga::palette, vga::palette + vga::paletteLen)
 {
-    QMetaObject::invokeMethod(
-        this,
-        [this]() {
-            transmute::write(m_socket,
-                             std::uint32_t(sizeof(std::uint8_t)   // package type
-                                           + sizeof(std::uint8_t) // client id
-                                           ));
-            transmute::write(m_socket, std::uint8_t(0));
-            transmute::write(m_socket, m_id);
-        },
-        Qt::QueuedConnection);
-
-    connect(m_socket, &QTcpSocket::readyRead, this, [this]() {
-        if (!m_currentPackageSize) {
-            if (const auto packageSize = transmute::read<std::uint32_t>(m_socket)) {
-                m_currentPackageSize = *packageSize;
-            }
-        }
+    watchSocket();
+    connect(socket, &QTcpSocket::disconnected, this, &WinClient::handleDisconnect);
+}
 
-        if (m_currentPackageSize) {
-            if (m_socket->bytesAvailable() >= *m_currentPackageSize) {
-                if (const auto frame = parseFrame(m_socket->read(*m_currentPackageSize),
-                                                  m_id,
-                                                  this)) {
-                    m_frame = *frame;
-                    if (width() != m_prevW) {
-                        emit widthChanged();
-                        m_prevW = width();
-                    }
-                    if (height() != m_prevH) {
-                        emit heightChanged();
-                        m_prevH = height();
-                    }
-                    emit imageChanged();
-                    emit titleChanged();
+WinClient::WinClient(UnixStream *socket, uint8_t clientId, QObject *parent)
+    : QObject(parent)
+    , m_socket(socket)
+    , m_unixStream(socket)
+    , m_id(clientId)
+    , m_palette(vga::palette, vga::palette + vga::paletteLen)
+{
+    // outlives server which accepted it
+    socket->setParent(this);
+    watchSocket();
+    connect(socket, &UnixStream::disconnected, this, &WinClient::handleDisconnect);
+}
+
+WinClient::~WinClient()
+{
+    detachShm();
+}
+
+void WinClient::watchSocket()
+{
+    connect(m_socket, &QIODevice::readyRead, this, [this]() {
+        while (m_socket) {
+            if (!m_currentPackageSize) {
+                if (const auto packageSize = transmute::read<std::uint32_t>(m_socket)) {
+                    m_currentPackageSize = *packageSize;
+                } else {
+                    break;
                 }
-                m_currentPackageSize = std::nullopt;
             }
+
+            if (m_socket->bytesAvailable() < *m_currentPackageSize)
+                break;
+
+            handlePackage(m_socket->read(*m_currentPackageSize));
+            m_currentPackageSize = std::nullopt;
         }
     });
+}
 
-    connect(m_socket, &QTcpSocket::disconnected, this, [this]() {
-        m_socket->deleteLater();
-        m_socket = nullptr;
-        emit dead(QPrivateSignal());
-    });
+void WinClient::handleDisconnect()
+{
+    m_socket->deleteLater();
+    m_socket = nullptr;
+    m_unixStream = nullptr;
+    emit dead(QPrivateSignal());
 }
 
 void WinClient::sendCloseEvent()
 {
+    // client expects init to be the first package
+    if (!m_protoVersion)
+        return;
+
     writePackage(m_socket, [](Package p) {
         p.write(out_package_types::Event);
         p.write(event_types::Close);
@@ -239,6 +934,9 @@ void WinClient::sendCloseEvent()
 
 void WinClient::sendResizeEvent(const QSize &newSize)
 {
+    if (!m_protoVersion)
+        return;
+
     writePackage(m_socket, [newSize](Package p) {
         p.write(out_package_types::Event);
         p.write(event_types::Resize);
@@ -247,33 +945,79 @@ void WinClient::sendResizeEvent(const QSize &newSize)
     });
 }
 
-void WinClient::sendMouseDownEvent(const QPoint &point)
+void WinClient::sendMouseDownEvent(const QPoint &point, Qt::MouseButton button)
 {
-    writePackage(m_socket, [point](Package p) {
+    if (!(m_capabilities & capabilities::MouseEvents))
+        return;
+    writePackage(m_socket, [point, button](Package p) {
         p.write(out_package_types::Event);
         p.write(event_types::MouseDown);
         p.write(quint16(point.x()));
         p.write(quint16(point.y()));
+        p.write(toWireButtons(button));
     });
 }
 
-void WinClient::sendMouseUpEvent(const QPoint &point)
+void WinClient::sendMouseUpEvent(const QPoint &point, Qt::MouseButton button)
 {
-    writePackage(m_socket, [point](Package p) {
+    if (!(m_capabilities & capabilities::MouseEvents))
+        return;
+    writePackage(m_socket, [point, button](Package p) {
         p.write(out_package_types::Event);
         p.write(event_types::MouseUp);
         p.write(quint16(point.x()));
         p.write(quint16(point.y()));
+        p.write(toWireButtons(button));
     });
 }
 
-void WinClient::sendMouseMoveEvent(const QPoint &point)
+void WinClient::sendMouseMoveEvent(const QPoint &point, Qt::MouseButtons buttons)
 {
-    writePackage(m_socket, [point](Package p) {
+    if (!(m_capabilities & capabilities::MouseEvents))
+        return;
+    writePackage(m_socket, [point, buttons](Package p) {
         p.write(out_package_types::Event);
         p.write(event_types::MouseMove);
         p.write(quint16(point.x()));
         p.write(quint16(point.y()));
+        p.write(toWireButtons(buttons));
+    });
+}
+
+void WinClient::sendKeyDownEvent(quint32 scanCode,
+                                 int key,
+                                 Qt::KeyboardModifiers modifiers,
+                                 const QString &text)
+{
+    sendKeyEvent(event_types::KeyDown, scanCode, key, modifiers, text);
+}
+
+void WinClient::sendKeyUpEvent(quint32 scanCode,
+                               int key,
+                               Qt::KeyboardModifiers modifiers,
+                               const QString &text)
+{
+    sendKeyEvent(event_types::KeyUp, scanCode, key, modifiers, text);
+}
+
+void WinClient::sendKeyEvent(quint8 type,
+                             quint32 scanCode,
+                             int key,
+                             Qt::KeyboardModifiers modifiers,
+                             const QString &text)
+{
+    if (!(m_capabilities & capabilities::KeyEvents))
+        return;
+
+    const auto textBytes = toWireKeyText(text);
+    writePackage(m_socket, [=](Package p) {
+        p.write(out_package_types::Event);
+        p.write(type);
+        p.write(scanCode);
+        p.write(toWireKey(key));
+        p.write(toWireModifiers(modifiers));
+        p.write(quint8(textBytes.size()));
+        p.writeBytes(textBytes);
     });
 }
 
@@ -4,7 +4,9 @@
 #include <QObject>
 #include <QtQmlIntegration>
 
+class QIODevice;
 class QTcpSocket;
+class UnixStream;
 
 class WinClient : public QObject
 {
@@ -20,6 +22,31 @@ class WinClient : public QObject
         QImage image;
     };
 
+    struct PaletteUpdate
+    {
+        std::uint8_t first;
+        QList<QRgb> colors;
+    };
+
+    /// Client's framebuffer mapped with `SHM_ATTACH`
+    struct ShmBuffer
+    {
+        std::uint8_t format;
+        std::uint8_t pixelSize;
+        std::uint16_t w;
+        std::uint16_t h;
+        /// Read only mapping of `len` bytes
+        const uchar *data;
+        qsizetype len;
+    };
+
+    struct Hello
+    {
+        std::uint8_t minVersion;
+        std::uint8_t maxVersion;
+        std::uint32_t capabilities;
+    };
+
     Q_PROPERTY(QImage image READ image NOTIFY imageChanged)
     Q_PROPERTY(int width READ width NOTIFY widthChanged)
     Q_PROPERTY(int height READ height NOTIFY heightChanged)
@@ -27,6 +54,9 @@ class WinClient : public QObject
 
 public:
     WinClient(QTcpSocket *socket, std::uint8_t clientId, QObject *parent = nullptr);
+    /// Client on unix socket, which may also share its framebuffer
+    WinClient(UnixStream *socket, std::uint8_t clientId, QObject *parent = nullptr);
+    ~WinClient() override;
 
     const QImage &image() const
     {
@@ -42,9 +72,17 @@ public:
 public slots:
     void sendCloseEvent();
     void sendResizeEvent(const QSize &newSize);
-    void sendMouseDownEvent(const QPoint &p);
-    void sendMouseUpEvent(const QPoint &p);
-    void sendMouseMoveEvent(const QPoint &p);
+    void sendMouseDownEvent(const QPoint &p, Qt::MouseButton button);
+    void sendMouseUpEvent(const QPoint &p, Qt::MouseButton button);
+    void sendMouseMoveEvent(const QPoint &p, Qt::MouseButtons buttons);
+    void sendKeyDownEvent(quint32 scanCode,
+                          int key,
+                          Qt::KeyboardModifiers modifiers,
+                          const QString &text);
+    void sendKeyUpEvent(quint32 scanCode,
+                        int key,
+                        Qt::KeyboardModifiers modifiers,
+                        const QString &text);
 
 signals:
     void dead(QPrivateSignal);
@@ -55,12 +93,43 @@ signals:
     void titleChanged();
 
 private:
-    static std::optional<Frame> parseFrame(QByteArray &&arr, std::uint8_t cid, QObject *giveMeErr);
+    void sendKeyEvent(quint8 type,
+                      quint32 scanCode,
+                      int key,
+                      Qt::KeyboardModifiers modifiers,
+                      const QString &text);
+    static std::optional<Hello> parseHello(QIODevice *dev, QObject *giveMeErr);
+    static std::optional<Frame> parseFrame(QIODevice *dev,
+                                           std::uint8_t protoVersion,
+                                           const std::optional<Frame> &prev,
+                                           QObject *giveMeErr);
+    static std::optional<Frame> parseRegion(QIODevice *dev,
+                                            std::uint8_t protoVersion,
+                                            const std::optional<Frame> &prev,
+                                            QObject *giveMeErr);
+    static std::optional<PaletteUpdate> parsePalette(QIODevice *dev, QObject *giveMeErr);
+    static std::optional<ShmBuffer> parseShmAttach(QIODevice *dev, int fd, QObject *giveMeErr);
+    static std::optional<QRect> parseShmPresent(QIODevice *dev, const ShmBuffer &shm, QObject *giveMeErr);
+    Frame shmFrame(const QRect &damage) const;
+    void detachShm();
+    void watchSocket();
+    void handleDisconnect();
+    void setFrame(Frame &&frame);
+    void setPalette(const PaletteUpdate &update);
+    void handlePackage(QByteArray &&arr);
+    void handleHello(const Hello &hello);
 
     std::optional<std::uint32_t> m_currentPackageSize;
     std::optional<Frame> m_frame;
-    QTcpSocket *m_socket = nullptr;
+    std::optional<std::uint8_t> m_protoVersion;
+    std::uint32_t m_capabilities = 0;
+    QIODevice *m_socket = nullptr;
+    /// Same as `m_socket` for clients able to pass descriptors
+    UnixStream *m_unixStream = nullptr;
+    std::optional<ShmBuffer> m_shm;
     std::uint8_t m_id = 0;
+    /// Colors of indexed frames, VGA palette until client sets its own
+    QList<QRgb> m_palette;
 
     int m_prevW = 0;
     int m_prevH = 0;
@@ -22,6 +22,40 @@ bool WinEventInterceptor::eventFilter(QObject *watched, QEvent *event)
         WANR_CLIENT_NOT_SET
         m_client->sendResizeEvent(resizeEvent->size());
         return true;
+    } else if (const auto mouseEvent = dynamic_cast<QMouseEvent *>(event)) {
+        WANR_CLIENT_NOT_SET
+        const auto pos = mouseEvent->position().toPoint();
+        switch (mouseEvent->type()) {
+        case QEvent::MouseButtonPress:
+            m_client->sendMouseDownEvent(pos, mouseEvent->button());
+            return true;
+        case QEvent::MouseButtonRelease:
+            m_client->sendMouseUpEvent(pos, mouseEvent->button());
+            return true;
+        case QEvent::MouseMove:
+            m_client->sendMouseMoveEvent(pos, mouseEvent->buttons());
+            return true;
+        default:
+            return false;
+        }
+    } else if (const auto keyEvent = dynamic_cast<QKeyEvent *>(event)) {
+        WANR_CLIENT_NOT_SET
+        switch (keyEvent->type()) {
+        case QEvent::KeyPress:
+            m_client->sendKeyDownEvent(keyEvent->nativeScanCode(),
+                                       keyEvent->key(),
+                                       keyEvent->modifiers(),
+                                       keyEvent->text());
+            return true;
+        case QEvent::KeyRelease:
+            m_client->sendKeyUpEvent(keyEvent->nativeScanCode(),
+                                     keyEvent->key(),
+                                     keyEvent->modifiers(),
+                                     keyEvent->text());
+            return true;
+        default:
+            return false;
+        }
     } else {
         return false;
     }
@@ -2,12 +2,14 @@
 
 #include <QQmlInfo>
 #include <QTcpServer>
+#include "unixstream.h"
 #include "winclient.h"
 
 MiniWinServer::MiniWinServer(QObject *parent)
     : QAbstractListModel{parent}
 {
     connect(this, &MiniWinServer::portChanged, this, [this]() { listen(m_port); });
+    connect(this, &MiniWinServer::socketPathChanged, this, [this]() { listenUnix(m_socketPath); });
 }
 
 int MiniWinServer::rowCount(const QModelIndex &parent) const
@@ -38,36 +40,11 @@ bool MiniWinServer::listen(quint16 port)
         m_server->deleteLater();
     }
 
-    beginResetModel();
-    for (const auto &c : m_clients)
-        c->deleteLater();
-    m_clients.clear();
-    endResetModel();
+    dropClients();
 
     m_server = new QTcpServer(this);
     connect(m_server, &QTcpServer::newConnection, this, [this]() {
-        const auto client = new WinClient(m_server->nextPendingConnection(), m_nextId++, this);
-
-        if (m_verbose) {
-            qInfo().nospace() << "Client connected with id: " << client->id() << ".";
-        }
-
-        connect(client, &WinClient::dead, this, [this, client]() {
-            if (m_verbose) {
-                qInfo().nospace() << "Client with id " << client->id() << " is dead.";
-            }
-
-            const auto i = m_clients.indexOf(client);
-            assert(i >= 0);
-            beginRemoveRows(QModelIndex(), i, i);
-            m_clients.removeAt(i);
-            client->deleteLater();
-            endRemoveRows();
-        });
-
-        beginInsertRows(QModelIndex(), m_clients.size(), m_clients.size());
-        m_clients.push_back(client);
-        endInsertRows();
+        addClient(new WinClient(m_server->nextPendingConnection(), m_nextId++, this));
     });
 
     if (!m_server->listen(QHostAddress::Any, port)) {
@@ -86,6 +63,79 @@ bool MiniWinServer::listen(quint16 port)
     return true;
 }
 
+bool MiniWinServer::listenUnix(const QString &path)
+{
+    if (m_unixServer) {
+        if (m_unixServer->fullServerName() == path)
+            return false;
+
+        if (m_verbose) {
+            qInfo().nospace() << "Changing socket: " << m_unixServer->fullServerName() << " -> "
+                              << path << ".";
+        }
+
+        // connected clients own their streams and stay
+        m_unixServer->deleteLater();
+        m_unixServer = nullptr;
+    }
+
+    if (path.isEmpty())
+        return false;
+
+    m_unixServer = new UnixServer(this);
+    connect(m_unixServer, &UnixServer::newStream, this, [this](UnixStream *stream) {
+        addClient(new WinClient(stream, m_nextId++, this));
+    });
+
+    // socket file left by previous run
+    QLocalServer::removeServer(path);
+    if (!m_unixServer->listen(path)) {
+        qmlWarning(this) << "Listen on " << path << " failed: " << m_unixServer->errorString();
+        m_unixServer->deleteLater();
+        m_unixServer = nullptr;
+        return false;
+    }
+
+    if (m_verbose) {
+        qInfo().nospace() << "Listening on socket: " << m_unixServer->fullServerName() << ".";
+    }
+
+    return true;
+}
+
+void MiniWinServer::dropClients()
+{
+    beginResetModel();
+    for (const auto &c : m_clients)
+        c->deleteLater();
+    m_clients.clear();
+    endResetModel();
+}
+
+void MiniWinServer::addClient(WinClient *client)
+{
+    if (m_verbose) {
+        qInfo().nospace() << "Client connected with id: " << client->id() << ".";
+    }
+
+    connect(client, &WinClient::dead, this, [this, client]() {
+        if (m_verbose) {
+            qInfo().nospace() << "Client with id " << client->id() << " is dead.";
+        }
+
+        const auto i = m_clients.indexOf(client);
+        assert(i >= 0);
+        beginRemoveRows(QModelIndex(), i, i);
+        m_clients.removeAt(i);
+        client->deleteLater();
+        endRemoveRows();
+    });
+
+    beginInsertRows(QModelIndex(), m_clients.size(), m_clients.size());
+    m_clients.push_back(client);
+    endInsertRows();
+}
+
 void MiniWinServer::classBegin() {}
 
 void MiniWinServer::componentComplete()
@@ -93,4 +143,7 @@ void MiniWinServer::componentComplete()
     if (!m_server) {
         listen(m_port);
     }
+    if (!m_unixServer) {
+        listenUnix(m_socketPath);
+    }
 }
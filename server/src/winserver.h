@@ -6,12 +6,15 @@
 
 class WinClient;
 class QTcpServer;
+class UnixServer;
 
 class MiniWinServer : public QAbstractListModel, public QQmlParserStatus
 {
     Q_OBJECT
     QML_ELEMENT
     Q_PROPERTY(quint16 port MEMBER m_port NOTIFY portChanged)
+    /// Unix socket for local clients, which may share framebuffers. Empty disables it
+    Q_PROPERTY(QString socketPath MEMBER m_socketPath NOTIFY socketPathChanged)
     Q_PROPERTY(bool verbose MEMBER m_verbose)
 
 public:
@@ -19,6 +22,7 @@ public:
 
 signals:
     void portChanged();
+    void socketPathChanged();
 
     // QAbstractItemModel interface
 public:
@@ -32,11 +36,17 @@ public:
 
 protected:
     bool listen(quint16 port);
+    bool listenUnix(const QString &path);
 
 private:
+    void dropClients();
+    void addClient(WinClient *client);
+
     QTcpServer *m_server = nullptr;
+    UnixServer *m_unixServer = nullptr;
     QVector<WinClient *> m_clients;
     std::uint8_t m_nextId = 0;
     quint16 m_port = 0;
+    QString m_socketPath;
     bool m_verbose = false;
 };
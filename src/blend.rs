@@ -0,0 +1,280 @@
+use core::ops::Range;
+
+use crate::{
+    canvas::{Canvas, Image},
+    pixel::Argb8888
+};
+
+/// `src` composited over `dst`, both with straight (not premultiplied) alpha like `Format::ARGB`
+pub fn over(src: Argb8888, dst: Argb8888) -> Argb8888 {
+    let sa = src.0 >> 24;
+    if sa == 0xff {
+        return src
+    }
+    if sa == 0 {
+        return dst
+    }
+    // destination weight and resulting alpha, both scaled by 255
+    let dw = (dst.0 >> 24) * (255 - sa);
+    let a = sa * 255 + dw;
+    let mut out = ((a + 127) / 255) << 24;
+    for shift in [0, 8, 16] {
+        let c = |p: Argb8888| (p.0 >> shift) & 0xff;
+        out |= ((c(src) * sa * 255 + c(dst) * dw + a / 2) / a) << shift;
+    }
+    Argb8888(out)
+}
+
+/// `color` with alpha multiplied by `coverage / 255`
+#[inline]
+pub fn with_coverage(color: Argb8888, coverage: u8) -> Argb8888 {
+    let a = ((color.0 >> 24) * coverage as u32 + 127) / 255;
+    Argb8888((color.0 & 0x00ff_ffff) | (a << 24))
+}
+
+/// Distance from origin scaled by 256
+#[inline]
+fn distance(dx: i64, dy: i64) -> i64 {
+    (((dx * dx + dy * dy) as u64) << 16).isqrt() as i64
+}
+
+/// Largest `x` with `x * x <= v`, -1 for negative `v`
+#[inline]
+fn isqrt(v: i64) -> i64 {
+    if v < 0 { -1 } else { (v as u64).isqrt() as i64 }
+}
+
+/// Coverage scaled by 256 as alpha multiplier
+#[inline]
+fn coverage(v: i64) -> u8 {
+    (v.clamp(0, 256) * 255 / 256) as u8
+}
+
+/// Steps `i` for which `start + dir * i` lies in `0..len`, `dir` is -1, 0 or 1
+fn steps_inside(start: i64, dir: i64, len: i64) -> Range<i64> {
+    match dir {
+        0 if (0..len).contains(&start) => i64::MIN..i64::MAX,
+        0 => 0..0,
+        1 => -start..len - start,
+        _ => start - len + 1..start + 1,
+    }
+}
+
+/// Alpha compositing, everything is clipped to the surface like other `Canvas` drawing
+impl Canvas<'_, Argb8888> {
+    /// Composite `color` over pixel at `x`, `y`
+    #[inline]
+    pub fn blend_pixel(&mut self, x: i32, y: i32, color: Argb8888) {
+        if let Some(dst) = self.pixel(x, y) {
+            self.set_pixel(x, y, over(color, dst));
+        }
+    }
+
+    #[inline]
+    fn blend_coverage(&mut self, x: i64, y: i64, color: Argb8888, coverage: u8) {
+        if coverage > 0 {
+            self.blend_pixel(x as i32, y as i32, with_coverage(color, coverage));
+        }
+    }
+
+    /// Anti-aliased (Wu) line including both ends
+    pub fn line_aa(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: Argb8888) {
+        let (w, h) = (self.w() as i32, self.h() as i32);
+        // completely on one side of surface
+        if (x0 < 0 && x1 < 0) || (y0 < 0 && y1 < 0) || (x0 >= w && x1 >= w) || (y0 >= h && y1 >= h) {
+            return
+        }
+        let ((x0, y0), (x1, y1)) = if y0 <= y1 { ((x0, y0), (x1, y1)) } else { ((x1, y1), (x0, y0)) };
+        let (x0, y0, x1, y1) = (x0 as i64, y0 as i64, x1 as i64, y1 as i64);
+        let xdir = if x1 < x0 { -1 } else { 1 };
+        let dx = (x1 - x0).abs();
+        let dy = y1 - y0;
+
+        let (w, h) = (w as i64, h as i64);
+
+        self.blend_pixel(x0 as i32, y0 as i32, color);
+        // no intermediate coverage for horizontal, vertical and diagonal lines
+        if dx == 0 || dy == 0 || dx == dy {
+            let sx = if dx == 0 { 0 } else { xdir };
+            let sy = if dy == 0 { 0 } else { 1 };
+            let (columns, rows) = (steps_inside(x0, sx, w), steps_inside(y0, sy, h));
+            for i in columns.start.max(rows.start).max(1)..columns.end.min(rows.end).min(dx.max(dy) + 1) {
+                self.blend_pixel((x0 + sx * i) as i32, (y0 + sy * i) as i32, color);
+            }
+            return
+        }
+
+        // only steps whose major axis position is on surface, position of step `i` is
+        // `i * step` scaled by 1 << 16 along minor axis, its fractional part gives coverage
+        if dy > dx {
+            let step = (dx << 16) / dy;
+            let rows = steps_inside(y0, 1, h);
+            for i in rows.start.max(1)..rows.end.min(dy) {
+                let (x, y) = (x0 + xdir * ((i * step) >> 16), y0 + i);
+                let weight = ((i * step) >> 8) as u8;
+                self.blend_coverage(x, y, color, !weight);
+                self.blend_coverage(x + xdir, y, color, weight);
+            }
+        } else {
+            let step = (dy << 16) / dx;
+            let columns = steps_inside(x0, xdir, w);
+            for i in columns.start.max(1)..columns.end.min(dx) {
+                let (x, y) = (x0 + xdir * i, y0 + ((i * step) >> 16));
+                let weight = ((i * step) >> 8) as u8;
+                self.blend_coverage(x, y, color, !weight);
+                self.blend_coverage(x, y + 1, color, weight);
+            }
+        }
+        self.blend_pixel(x1 as i32, y1 as i32, color);
+    }
+
+    /// Calls `f(canvas, dx, dy, inside)` for surface pixels closer than `r + 1` to center,
+    /// `inside` is true when `|dx|` is not greater than `inner(dy)`
+    fn circle_rows(&mut self, cx: i32, cy: i32, r: i32, inner: impl Fn(i64) -> i64, mut f: impl FnMut(&mut Self, i64, i64, bool)) {
+        if r < 0 {
+            return
+        }
+        let (cx, cy, r) = (cx as i64, cy as i64, r as i64);
+        let (w, h) = (self.w() as i64, self.h() as i64);
+        for dy in (-r - 1).max(-cy)..=(r + 1).min(h - 1 - cy) {
+            let outer = isqrt((r + 1) * (r + 1) - dy * dy);
+            let inner = inner(dy);
+            for dx in (-outer).max(-cx)..=outer.min(w - 1 - cx) {
+                f(self, dx, dy, dx.abs() <= inner);
+            }
+        }
+    }
+
+    /// Anti-aliased one pixel wide circle outline
+    pub fn circle_aa(&mut self, cx: i32, cy: i32, r: i32, color: Argb8888) {
+        let r_fixed = r as i64 * 256;
+        let inner = |dy: i64| if r > 0 { isqrt((r as i64 - 1).pow(2) - dy * dy) } else { -1 };
+        self.circle_rows(cx, cy, r, inner, |canvas, dx, dy, inside| {
+            if !inside {
+                let cov = coverage(256 - (distance(dx, dy) - r_fixed).abs());
+                canvas.blend_coverage(cx as i64 + dx, cy as i64 + dy, color, cov);
+            }
+        });
+    }
+
+    /// Filled circle with anti-aliased edge, covers the same pixels as `circle_aa` outline
+    pub fn fill_circle_aa(&mut self, cx: i32, cy: i32, r: i32, color: Argb8888) {
+        let r_fixed = r as i64 * 256;
+        self.circle_rows(cx, cy, r, |dy| isqrt((r as i64).pow(2) - dy * dy), |canvas, dx, dy, inside| {
+            let cov = if inside { 255 } else { coverage(r_fixed + 256 - distance(dx, dy)) };
+            canvas.blend_coverage(cx as i64 + dx, cy as i64 + dy, color, cov);
+        });
+    }
+
+    /// Composite `src` over canvas with top left corner at `x`, `y`
+    pub fn blit_over(&mut self, x: i32, y: i32, src: &Image<Argb8888>) {
+        for sy in y.saturating_neg().max(0)..(src.h() as i32).min((self.h() as i32).saturating_sub(y)) {
+            for sx in x.saturating_neg().max(0)..(src.w() as i32).min((self.w() as i32).saturating_sub(x)) {
+                if let Some(color) = src.pixel(sx, sy) {
+                    self.blend_pixel(x + sx, y + sy, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{env, fmt::Write, fs, string::String, vec, vec::Vec};
+
+    use super::*;
+
+    /// Compare canvas with `tests/golden/<name>.txt`, `UPDATE_GOLDEN=1` rewrites the file
+    fn check_golden(name: &str, canvas: &Canvas<Argb8888>) {
+        let mut rendered = String::new();
+        for y in 0..canvas.h() as i32 {
+            let row: Vec<String> = (0..canvas.w() as i32)
+                .map(|x| std::format!("{:08x}", canvas.pixel(x, y).unwrap().0))
+                .collect();
+            writeln!(rendered, "{}", row.join(" ")).unwrap();
+        }
+        let path = std::format!("{}/tests/golden/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, &rendered).unwrap();
+        }
+        let expected = fs::read_to_string(&path).unwrap();
+        assert!(rendered == expected, "{} differs from golden image:\n{}", name, rendered);
+    }
+
+    #[test]
+    fn over_test() {
+        let black = Argb8888(0xff00_0000);
+        assert_eq!(over(Argb8888(0xff12_3456), black), Argb8888(0xff12_3456));
+        assert_eq!(over(Argb8888(0x0012_3456), black), black);
+        assert_eq!(over(Argb8888(0x80ff_ffff), black), Argb8888(0xff80_8080));
+        // nothing below keeps color
+        assert_eq!(over(Argb8888(0x80ff_8000), Argb8888(0)), Argb8888(0x80ff_8000));
+        assert_eq!(over(Argb8888(0x80ff_0000), Argb8888(0x8000_00ff)), Argb8888(0xc0aa_0055));
+        assert_eq!(with_coverage(Argb8888(0x80ff_0000), 128), Argb8888(0x40ff_0000));
+    }
+
+    #[test]
+    fn line_aa_test() {
+        let mut pixels = vec![Argb8888(0); 16 * 12];
+        let mut canvas = Canvas::new(16, 12, &mut pixels).unwrap();
+        canvas.line_aa(1, 1, 14, 6, Argb8888(0xffff_ffff));
+        canvas.line_aa(5, 1, 2, 10, Argb8888(0x80ff_0000));
+        canvas.line_aa(0, 11, 15, 11, Argb8888(0xff00_ff00));
+        // clipped
+        canvas.line_aa(10, 13, 20, 7, Argb8888(0xff00_00ff));
+        check_golden("line_aa", &canvas);
+
+        // reversed line covers the same pixels
+        let mut reversed = vec![Argb8888(0); 16 * 12];
+        let mut canvas = Canvas::new(16, 12, &mut reversed).unwrap();
+        canvas.line_aa(14, 6, 1, 1, Argb8888(0xffff_ffff));
+        canvas.line_aa(2, 10, 5, 1, Argb8888(0x80ff_0000));
+        canvas.line_aa(15, 11, 0, 11, Argb8888(0xff00_ff00));
+        canvas.line_aa(20, 7, 10, 13, Argb8888(0xff00_00ff));
+        assert_eq!(reversed, pixels);
+    }
+
+    #[test]
+    fn line_aa_clip_test() {
+        let mut pixels = vec![Argb8888(0); 8 * 4];
+        let mut canvas = Canvas::new(8, 4, &mut pixels).unwrap();
+        // only steps on surface are taken, so these return at once
+        canvas.line_aa(-1_000_000_000, 1, 1_000_000_000, 2, Argb8888(0xffff_ffff));
+        canvas.line_aa(i32::MIN, i32::MIN, i32::MAX, i32::MAX, Argb8888(0xffff_ffff));
+        canvas.line_aa(6, i32::MAX, 6, i32::MIN, Argb8888(0xffff_ffff));
+        let rendered: Vec<String> = (0..4)
+            .map(|y| (0..8).map(|x| if canvas.pixel(x, y).unwrap().0 == 0xffff_ffff { '#' } else { '.' }).collect())
+            .collect();
+        assert_eq!(rendered, [
+            "#.....#.",
+            "########",
+            "..#...#.",
+            "...#..#.",
+        ]);
+    }
+
+    #[test]
+    fn circle_aa_test() {
+        let mut pixels = vec![Argb8888(0xff00_0000); 16 * 16];
+        let mut canvas = Canvas::new(16, 16, &mut pixels).unwrap();
+        canvas.circle_aa(6, 6, 5, Argb8888(0xffff_ffff));
+        canvas.fill_circle_aa(14, 14, 4, Argb8888(0x80ff_0000));
+        canvas.fill_circle_aa(2, 13, 0, Argb8888(0xff00_ff00));
+        canvas.circle_aa(-100, 0, 3, Argb8888(0xffff_ffff));
+        check_golden("circle_aa", &canvas);
+    }
+
+    #[test]
+    fn blit_over_test() {
+        let sprite: Vec<Argb8888> = (0..16).map(|i| Argb8888(((i * 17) << 24) | 0x00ff_ff00)).collect();
+        let sprite = Image::new(4, 4, &sprite).unwrap();
+        let mut pixels = vec![Argb8888(0xff00_00ff); 6 * 5];
+        let mut canvas = Canvas::new(6, 5, &mut pixels).unwrap();
+        canvas.blit_over(-1, 2, &sprite);
+        canvas.blit_over(4, -2, &sprite);
+        canvas.blit_over(i32::MAX, i32::MIN, &sprite);
+        check_golden("blit_over", &canvas);
+    }
+}
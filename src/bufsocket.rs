@@ -1,20 +1,23 @@
-use core::{mem::{MaybeUninit, size_of}, slice};
+use core::{mem::size_of, slice, time::Duration};
 
 use crate::{
     socket::{
         Socket,
+        Addr,
         WriteError,
         ReadError,
         FlagsChangeError,
         ConnectError
     },
-    utils::CyclicBuf
+    utils::CyclicBuf,
+    sys::io_sys::Fd
 };
 
+/// `CHUNK_LEN` is max count of bytes requested from socket by one read
 pub struct BufSocket<const CAPACITY: usize, const CHUNK_LEN: usize> {
     s: Socket,
     buf: CyclicBuf<u8, CAPACITY>,
-    chunk: [u8; CHUNK_LEN]
+    eof: bool
 }
 
 impl<const CAPACITY: usize, const CHUNK_LEN: usize> BufSocket<CAPACITY, CHUNK_LEN> {
@@ -25,14 +28,20 @@ impl<const CAPACITY: usize, const CHUNK_LEN: usize> BufSocket<CAPACITY, CHUNK_LE
     }
 
     #[inline]
-    pub fn connect(ip: [u8; 4], port: u16) -> Result<Self, ConnectError> {
-        match Socket::connect(ip, port) {
-            Ok(s) => Ok(Self {
-                s,
-                buf: Default::default(),
-                chunk: unsafe { MaybeUninit::uninit().assume_init() }
-            }),
-            Err(err) => Err(err),
+    pub fn connect(addr: Addr) -> Result<Self, ConnectError> {
+        Socket::connect(addr).map(Self::from_socket)
+    }
+
+    #[inline]
+    pub fn connect_unix(path: &str) -> Result<Self, ConnectError> {
+        Self::connect(Addr::Unix(path))
+    }
+
+    fn from_socket(s: Socket) -> Self {
+        Self {
+            s,
+            buf: Default::default(),
+            eof: false
         }
     }
 
@@ -46,11 +55,42 @@ impl<const CAPACITY: usize, const CHUNK_LEN: usize> BufSocket<CAPACITY, CHUNK_LE
         self.buf.len()
     }
 
+    /// Max count of bytes that can be buffered at once
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    #[inline]
+    pub fn wait_readable(&mut self, timeout: Duration) -> Result<bool, ReadError> {
+        self.s.wait_readable(timeout)
+    }
+
     #[inline]
     pub fn write_bytes(&mut self, b: &[u8]) -> Result<usize, WriteError> {
         self.s.write_bytes(b)
     }
 
+    #[inline]
+    pub fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<usize, WriteError> {
+        self.s.write_vectored(bufs)
+    }
+
+    #[inline]
+    pub fn write_vectored_with_fd(&mut self, bufs: &[&[u8]], fd: Fd) -> Result<usize, WriteError> {
+        self.s.write_vectored_with_fd(bufs, fd)
+    }
+
+    #[inline]
+    pub fn write_all(&mut self, b: &[u8]) -> Result<(), WriteError> {
+        self.s.write_all(b)
+    }
+
+    #[inline]
+    pub fn write_all_vectored(&mut self, bufs: &mut [&[u8]]) -> Result<(), WriteError> {
+        self.s.write_all_vectored(bufs)
+    }
+
     #[inline]
     pub fn write_transmuted<T: Sized>(&mut self, val: T) -> Result<usize, WriteError> {
         self.s.write_transmuted(val)
@@ -71,6 +111,16 @@ impl<const CAPACITY: usize, const CHUNK_LEN: usize> BufSocket<CAPACITY, CHUNK_LE
         }
     }
 
+    /// Same as `read_transmuted`, but only if `T` fits in `limit` bytes. `limit` is decreased by size of `T`
+    #[inline]
+    pub fn read_transmuted_limited<T: Sized>(&mut self, dst: &mut T, limit: &mut usize) -> bool {
+        if *limit < size_of::<T>() || !self.read_transmuted(dst) {
+            return false
+        }
+        *limit -= size_of::<T>();
+        true
+    }
+
     #[inline]
     pub fn peek_transmuted<T: Sized>(&mut self, dst: &mut T) -> bool {
         if self.buf.len() >= size_of::<T>() {
@@ -86,15 +136,32 @@ impl<const CAPACITY: usize, const CHUNK_LEN: usize> BufSocket<CAPACITY, CHUNK_LE
         }
     }
 
+    /// True if peer closed connection. Already buffered bytes still can be read
+    #[inline]
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Returns `ReadError::Eof` once peer closed connection and on every call after that
     #[inline]
     pub fn bufferize_chunk(&mut self) -> Result<usize, ReadError> {
-        let can_read_bytes = self.buf.push_ability();
+        if self.eof {
+            return Err(ReadError::Eof)
+        }
+
+        let spare = self.buf.spare_slice();
+        let can_read_bytes = spare.len().min(CHUNK_LEN);
 
         Ok(if can_read_bytes > 0 {
-            let bytes_read = self.s.read_bytes(&mut self.chunk[0..can_read_bytes.min(CHUNK_LEN)])?;
-            for i in 0..bytes_read {
-                assert!(self.buf.push(self.chunk[i]));
-            }
+            let bytes_read = match self.s.read_uninit(&mut spare[..can_read_bytes]) {
+                Ok(bytes_read) => bytes_read,
+                Err(ReadError::Eof) => {
+                    self.eof = true;
+                    return Err(ReadError::Eof)
+                },
+                Err(err) => return Err(err),
+            };
+            unsafe { self.buf.commit(bytes_read) };
             bytes_read
         } else { 0 })
     }
@@ -122,12 +189,14 @@ impl<const CAPACITY: usize, const CHUNK_LEN: usize> BufSocket<CAPACITY, CHUNK_LE
         self.buf.peek(b)
     }
 
+    /// Drop up to `count` buffered bytes. Returns number of dropped bytes
+    #[inline]
+    pub fn discard(&mut self, count: usize) -> usize {
+        self.buf.consume(count)
+    }
+
     #[inline]
     pub fn read_bytes(&mut self, b: &mut[u8]) -> usize {
-        let bytes_to_read = self.buf.len().min(b.len());
-        for i in 0..bytes_to_read {
-            b[i] = self.buf.pop().unwrap();
-        }
-        bytes_to_read
+        self.buf.drain_into(b)
     }
 }
@@ -0,0 +1,627 @@
+use crate::pixel::{
+    self,
+    Argb8888,
+    Gray8,
+    Mono,
+    Pixel,
+    PremulRgba8888,
+    Rgb565,
+    Rgb888,
+    Rgba8888,
+    Vga8
+};
+
+/// Pixel storage canvas can draw into
+pub trait Plot: Pixel {
+    /// Value of single pixel, the pixel itself unless it is packed
+    type Color: Copy + PartialEq;
+
+    /// Set pixel `x` of row starting at `row[0]`
+    fn put(row: &mut [Self], x: usize, color: Self::Color);
+
+    fn take(row: &[Self], x: usize) -> Self::Color;
+}
+
+macro_rules! plot_impl {
+    ($($name:ident),*) => {
+        $(
+            impl Plot for $name {
+                type Color = $name;
+
+                #[inline]
+                fn put(row: &mut [Self], x: usize, color: Self::Color) {
+                    row[x] = color;
+                }
+
+                #[inline]
+                fn take(row: &[Self], x: usize) -> Self::Color {
+                    row[x]
+                }
+            }
+        )*
+    };
+}
+
+plot_impl!(Gray8, Argb8888, Vga8, Rgb565, Rgb888, Rgba8888, PremulRgba8888);
+
+impl Plot for Mono {
+    /// `true` is white
+    type Color = bool;
+
+    #[inline]
+    fn put(row: &mut [Self], x: usize, color: bool) {
+        row[x / 8].set((x % 8) as u8, color);
+    }
+
+    #[inline]
+    fn take(row: &[Self], x: usize) -> bool {
+        row[x / 8].get((x % 8) as u8)
+    }
+}
+
+/// Read only `w` x `h` surface, e.g. sprite for `Canvas::blit`
+#[derive(Debug, Clone, Copy)]
+pub struct Image<'a, P: Pixel> {
+    w: u16,
+    h: u16,
+    pixels: &'a [P]
+}
+
+impl<'a, P: Plot> Image<'a, P> {
+    /// `None` if `pixels` does not hold exactly `w` x `h` pixels
+    pub fn new(w: u16, h: u16, pixels: &'a [P]) -> Option<Self> {
+        if pixels.len() == pixel::surface_len::<P>(w, h) { Some(Self { w, h, pixels }) } else { None }
+    }
+
+    #[inline]
+    pub fn w(&self) -> u16 {
+        self.w
+    }
+
+    #[inline]
+    pub fn h(&self) -> u16 {
+        self.h
+    }
+
+    #[inline]
+    pub fn pixels(&self) -> &'a [P] {
+        self.pixels
+    }
+
+    /// Pixel at `x`, `y`, `None` outside of image
+    pub fn pixel(&self, x: i32, y: i32) -> Option<P::Color> {
+        if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+            return None
+        }
+        Some(P::take(&self.pixels[y as usize * pixel::row_len::<P>(self.w)..], x as usize))
+    }
+}
+
+/// Max vertex count of `Canvas::fill_polygon`
+pub const MAX_POLYGON_VERTICES: usize = 64;
+
+#[derive(Debug)]
+pub struct TooManyVertices;
+
+/// Draws into `w` x `h` pixel buffer. Everything is clipped to the surface,
+/// so shapes may lie partially or completely outside of it
+pub struct Canvas<'a, P: Plot> {
+    w: u16,
+    h: u16,
+    pixels: &'a mut [P]
+}
+
+impl<'a, P: Plot> Canvas<'a, P> {
+    /// `None` if `pixels` does not hold exactly `w` x `h` pixels
+    pub fn new(w: u16, h: u16, pixels: &'a mut [P]) -> Option<Self> {
+        if pixels.len() == pixel::surface_len::<P>(w, h) { Some(Self { w, h, pixels }) } else { None }
+    }
+
+    #[inline]
+    pub fn w(&self) -> u16 {
+        self.w
+    }
+
+    #[inline]
+    pub fn h(&self) -> u16 {
+        self.h
+    }
+
+    /// Row major pixels, ready for `Client::present`
+    #[inline]
+    pub fn pixels(&self) -> &[P] {
+        self.pixels
+    }
+
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [P] {
+        self.pixels
+    }
+
+    #[inline]
+    pub fn image(&self) -> Image<'_, P> {
+        Image { w: self.w, h: self.h, pixels: self.pixels }
+    }
+
+    /// Pixel at `x`, `y`, `None` outside of surface
+    #[inline]
+    pub fn pixel(&self, x: i32, y: i32) -> Option<P::Color> {
+        self.image().pixel(x, y)
+    }
+
+    #[inline]
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.w as i32 && y < self.h as i32
+    }
+
+    /// Caller checks bounds
+    #[inline]
+    fn put(&mut self, x: i32, y: i32, color: P::Color) {
+        let row = y as usize * pixel::row_len::<P>(self.w);
+        P::put(&mut self.pixels[row..], x as usize, color);
+    }
+
+    #[inline]
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: P::Color) {
+        if self.contains(x, y) {
+            self.put(x, y, color);
+        }
+    }
+
+    pub fn fill(&mut self, color: P::Color) {
+        for y in 0..self.h as i32 {
+            self.span(0, self.w as i32 - 1, y, color);
+        }
+    }
+
+    /// Horizontal line from `x0` to `x1` inclusive
+    fn span(&mut self, x0: i32, x1: i32, y: i32, color: P::Color) {
+        if y < 0 || y >= self.h as i32 {
+            return
+        }
+        let (x0, x1) = (x0.min(x1).max(0), x0.max(x1).min(self.w as i32 - 1));
+        for x in x0..=x1 {
+            self.put(x, y, color);
+        }
+    }
+
+    /// Line including both ends through pixels nearest to it (as Bresenham, ties round to larger coordinate).
+    /// Major axis is clipped to surface before stepping, so long lines cost at most surface size
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: P::Color) {
+        // completely on one side of surface
+        if (x0 < 0 && x1 < 0) || (y0 < 0 && y1 < 0)
+            || (x0 >= self.w as i32 && x1 >= self.w as i32) || (y0 >= self.h as i32 && y1 >= self.h as i32) {
+            return
+        }
+        if y0 == y1 {
+            return self.span(x0, x1, y0, color)
+        }
+
+        let (dx, dy) = (x1 as i64 - x0 as i64, y1 as i64 - y0 as i64);
+        if dx.abs() >= dy.abs() {
+            for x in x0.min(x1).max(0)..=x0.max(x1).min(self.w as i32 - 1) {
+                self.set_pixel(x, minor_at(x, x0, y0, dx, dy), color);
+            }
+        } else {
+            for y in y0.min(y1).max(0)..=y0.max(y1).min(self.h as i32 - 1) {
+                self.set_pixel(minor_at(y, y0, x0, dy, dx), y, color);
+            }
+        }
+    }
+
+    /// Outline of `w` x `h` rectangle with top left corner at `x`, `y`
+    pub fn rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: P::Color) {
+        if w <= 0 || h <= 0 {
+            return
+        }
+        // edges past i32 range are off surface anyway
+        let (x1, y1) = (x.saturating_add(w - 1), y.saturating_add(h - 1));
+        self.span(x, x1, y, color);
+        self.span(x, x1, y1, color);
+        for yi in y.saturating_add(1).max(0)..y1.min(self.h as i32) {
+            self.set_pixel(x, yi, color);
+            self.set_pixel(x1, yi, color);
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: P::Color) {
+        if w <= 0 || h <= 0 {
+            return
+        }
+        for yi in y.max(0)..y.saturating_add(h).min(self.h as i32) {
+            self.span(x, x.saturating_add(w - 1), yi, color);
+        }
+    }
+
+    /// Call `f` with points of quarter of ellipse (midpoint algorithm), `x` and `y` are not negative.
+    /// Decision terms of radii above about 1.6 million overflow i64
+    fn ellipse_quarter(rx: i32, ry: i32, mut f: impl FnMut(i32, i32)) {
+        let (rx2, ry2) = (rx as i128 * rx as i128, ry as i128 * ry as i128);
+        let (mut x, mut y) = (0i128, ry as i128);
+        let (mut px, mut py) = (0, 2 * rx2 * y);
+
+        // slope above -1, step x
+        let mut p = ry2 - rx2 * ry as i128 + rx2 / 4;
+        while px < py {
+            f(x as i32, y as i32);
+            x += 1;
+            px += 2 * ry2;
+            if p < 0 {
+                p += ry2 + px;
+            } else {
+                y -= 1;
+                py -= 2 * rx2;
+                p += ry2 + px - py;
+            }
+        }
+
+        // step y
+        p = (ry2 * (2 * x + 1) * (2 * x + 1) + 4 * rx2 * (y - 1) * (y - 1) - 4 * rx2 * ry2) / 4;
+        while y >= 0 {
+            f(x as i32, y as i32);
+            y -= 1;
+            py -= 2 * rx2;
+            if p > 0 {
+                p += rx2 - py;
+            } else {
+                x += 1;
+                px += 2 * ry2;
+                p += rx2 - py + px;
+            }
+        }
+    }
+
+    /// True if ellipse can not touch surface or has negative radius
+    fn ellipse_outside(&self, cx: i32, cy: i32, rx: i32, ry: i32) -> bool {
+        let (cx, cy, rx, ry) = (cx as i64, cy as i64, rx as i64, ry as i64);
+        rx < 0 || ry < 0 || cx + rx < 0 || cy + ry < 0 || cx - rx >= self.w as i64 || cy - ry >= self.h as i64
+    }
+
+    /// Outline of ellipse centered at `cx`, `cy` with radii `rx`, `ry`
+    pub fn ellipse(&mut self, cx: i32, cy: i32, rx: i32, ry: i32, color: P::Color) {
+        if self.ellipse_outside(cx, cy, rx, ry) {
+            return
+        }
+        // points past i32 range are off surface anyway
+        if rx == 0 || ry == 0 {
+            return self.line(cx.saturating_sub(rx), cy.saturating_sub(ry), cx.saturating_add(rx), cy.saturating_add(ry), color)
+        }
+        Self::ellipse_quarter(rx, ry, |x, y| {
+            self.set_pixel(cx.saturating_add(x), cy.saturating_add(y), color);
+            self.set_pixel(cx.saturating_sub(x), cy.saturating_add(y), color);
+            self.set_pixel(cx.saturating_add(x), cy.saturating_sub(y), color);
+            self.set_pixel(cx.saturating_sub(x), cy.saturating_sub(y), color);
+        });
+    }
+
+    pub fn fill_ellipse(&mut self, cx: i32, cy: i32, rx: i32, ry: i32, color: P::Color) {
+        if self.ellipse_outside(cx, cy, rx, ry) {
+            return
+        }
+        if rx == 0 || ry == 0 {
+            return self.line(cx.saturating_sub(rx), cy.saturating_sub(ry), cx.saturating_add(rx), cy.saturating_add(ry), color)
+        }
+        Self::ellipse_quarter(rx, ry, |x, y| {
+            self.span(cx.saturating_sub(x), cx.saturating_add(x), cy.saturating_add(y), color);
+            self.span(cx.saturating_sub(x), cx.saturating_add(x), cy.saturating_sub(y), color);
+        });
+    }
+
+    #[inline]
+    pub fn circle(&mut self, cx: i32, cy: i32, r: i32, color: P::Color) {
+        self.ellipse(cx, cy, r, r, color);
+    }
+
+    #[inline]
+    pub fn fill_circle(&mut self, cx: i32, cy: i32, r: i32, color: P::Color) {
+        self.fill_ellipse(cx, cy, r, r, color);
+    }
+
+    /// Closed outline through `points`
+    pub fn polygon(&mut self, points: &[(i32, i32)], color: P::Color) {
+        for (i, &(x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(i + 1) % points.len()];
+            self.line(x0, y0, x1, y1, color);
+        }
+    }
+
+    /// Fill pixels whose centers are inside polygon by even-odd rule
+    pub fn fill_polygon(&mut self, points: &[(i32, i32)], color: P::Color) -> Result<(), TooManyVertices> {
+        if points.len() > MAX_POLYGON_VERTICES {
+            return Err(TooManyVertices)
+        }
+        let Some(top) = points.iter().map(|p| p.1).min() else { return Ok(()) };
+        let bottom = points.iter().map(|p| p.1).max().unwrap_or(top);
+
+        let mut crossings = [0i32; MAX_POLYGON_VERTICES];
+        for y in top.max(0)..bottom.min(self.h as i32) {
+            // edges crossing row center, twice scaled so center is integer
+            let cy = 2 * y as i64 + 1;
+            let mut count = 0;
+            for (i, &a) in points.iter().enumerate() {
+                let b = points[(i + 1) % points.len()];
+                // downwards, so both directions of edge round the same
+                let ((x0, y0), (x1, y1)) = if a.1 <= b.1 { (a, b) } else { (b, a) };
+                if 2 * y0 as i64 <= cy && cy < 2 * y1 as i64 {
+                    // first pixel with center right of crossing: ceil(crossing - 0.5)
+                    let dy = y1 as i64 - y0 as i64;
+                    let num = (cy - 2 * y0 as i64) * (x1 as i64 - x0 as i64) - dy;
+                    crossings[count] = (x0 as i64 - (-num).div_euclid(2 * dy)) as i32;
+                    count += 1;
+                }
+            }
+            let crossings = &mut crossings[..count];
+            crossings.sort_unstable();
+            for pair in crossings.chunks_exact(2) {
+                if pair[0] < pair[1] {
+                    self.span(pair[0], pair[1] - 1, y, color);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy `src` with top left corner at `x`, `y`
+    pub fn blit(&mut self, x: i32, y: i32, src: &Image<P>) {
+        for sy in y.saturating_neg().max(0)..(src.h as i32).min((self.h as i32).saturating_sub(y)) {
+            for sx in x.saturating_neg().max(0)..(src.w as i32).min((self.w as i32).saturating_sub(x)) {
+                let color = P::take(&src.pixels[sy as usize * pixel::row_len::<P>(src.w)..], sx as usize);
+                self.put(x + sx, y + sy, color);
+            }
+        }
+    }
+}
+
+/// Minor coordinate at major coordinate `m` of line starting at `m0`, `b0` with deltas `dm` (not zero), `db`.
+/// Exact value is rounded half up, so both directions of line give the same pixels
+fn minor_at(m: i32, m0: i32, b0: i32, dm: i64, db: i64) -> i32 {
+    let (num, den) = ((m as i128 - m0 as i128) * db as i128, dm as i128);
+    let (num, den) = if den < 0 { (-num, -den) } else { (num, den) };
+    // between both ends, so in i32 range
+    (b0 as i128 + (2 * num + den).div_euclid(2 * den)) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{string::String, vec, vec::Vec};
+
+    use super::*;
+
+    fn render(canvas: &Canvas<Gray8>) -> Vec<String> {
+        (0..canvas.h() as i32)
+            .map(|y| (0..canvas.w() as i32).map(|x| if canvas.pixel(x, y).unwrap().0 > 0 { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    const ON: Gray8 = Gray8(255);
+
+    #[test]
+    fn size_test() {
+        let mut pixels = vec![Gray8(0); 12];
+        assert!(Canvas::new(4, 4, &mut pixels).is_none());
+        assert!(Canvas::new(4, 3, &mut pixels).is_some());
+        let mut mono = vec![Mono(0); 6];
+        assert!(Canvas::new(9, 3, &mut mono).is_some());
+    }
+
+    #[test]
+    fn line_test() {
+        let mut pixels = vec![Gray8(0); 36];
+        let mut canvas = Canvas::new(6, 6, &mut pixels).unwrap();
+        canvas.line(0, 0, 5, 2, ON);
+        canvas.line(5, 5, 0, 5, ON);
+        canvas.line(0, 5, 0, 3, ON);
+        assert_eq!(render(&canvas), [
+            "##....",
+            "..##..",
+            "....##",
+            "#.....",
+            "#.....",
+            "######",
+        ]);
+
+        // reversed line covers the same pixels
+        let mut a = vec![Gray8(0); 36];
+        let mut b = vec![Gray8(0); 36];
+        Canvas::new(6, 6, &mut a).unwrap().line(1, 0, 4, 5, ON);
+        Canvas::new(6, 6, &mut b).unwrap().line(4, 5, 1, 0, ON);
+        assert_eq!(a, b);
+        // with ties halfway between pixels
+        Canvas::new(6, 6, &mut a).unwrap().line(0, 1, 4, 2, ON);
+        Canvas::new(6, 6, &mut b).unwrap().line(4, 2, 0, 1, ON);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn clip_test() {
+        let mut pixels = vec![Gray8(0); 16];
+        let mut canvas = Canvas::new(4, 4, &mut pixels).unwrap();
+        canvas.line(-2, -2, 5, 5, ON);
+        canvas.line(-100, 100, 100, 100, ON);
+        canvas.line(i32::MIN, 0, i32::MIN, 3, ON);
+        canvas.rect(2, 2, 10, 10, ON);
+        canvas.fill_rect(-5, -5, 6, 1, ON);
+        canvas.circle(100, 100, 5, ON);
+        canvas.fill_circle(-1, 3, 1, ON);
+        canvas.set_pixel(4, 0, ON);
+        assert_eq!(render(&canvas), [
+            "#...",
+            ".#..",
+            "..##",
+            "#.##",
+        ]);
+
+        // coordinates near i32 limits do not overflow
+        let sprite_pixels = [ON; 4];
+        let sprite = Image::new(2, 2, &sprite_pixels).unwrap();
+        let mut pixels = vec![Gray8(0); 16];
+        let mut canvas = Canvas::new(4, 4, &mut pixels).unwrap();
+        canvas.rect(1, 1, i32::MAX, i32::MAX, ON);
+        canvas.rect(i32::MAX, i32::MAX, i32::MAX, i32::MAX, ON);
+        canvas.rect(i32::MIN, i32::MIN, i32::MAX, i32::MAX, ON);
+        canvas.fill_rect(i32::MAX, 0, i32::MAX, 1, ON);
+        canvas.fill_rect(i32::MIN, i32::MIN, i32::MAX, i32::MAX, ON);
+        canvas.fill_rect(3, 3, i32::MAX, i32::MAX, ON);
+        canvas.blit(i32::MAX, i32::MAX, &sprite);
+        canvas.blit(i32::MIN, i32::MIN, &sprite);
+        canvas.blit(i32::MAX, i32::MIN, &sprite);
+        canvas.blit(-1, i32::MIN, &sprite);
+        assert_eq!(render(&canvas), [
+            "....",
+            ".###",
+            ".#..",
+            ".#.#",
+        ]);
+
+        // ellipses and lines near i32 limits neither overflow nor step through their off surface part
+        let mut pixels = vec![Gray8(0); 16];
+        let mut canvas = Canvas::new(4, 4, &mut pixels).unwrap();
+        canvas.circle(i32::MAX, 0, 5, ON);
+        canvas.circle(i32::MIN, i32::MIN, 5, ON);
+        canvas.fill_circle(i32::MAX, i32::MAX, 5, ON);
+        canvas.fill_ellipse(i32::MIN, 1, i32::MAX, 0, ON);
+        canvas.ellipse(2, i32::MAX, 0, i32::MAX, ON);
+        canvas.circle(-1_999_997, 1, 2_000_000, ON);
+        canvas.line(-1_000_000_000, 0, 1_000_000_000, 1, ON);
+        canvas.line(i32::MIN, i32::MIN, i32::MAX, i32::MAX, ON);
+        canvas.line(i32::MAX, 3, i32::MIN, 3, ON);
+        assert_eq!(render(&canvas), [
+            "#.##",
+            "####",
+            "..##",
+            "####",
+        ]);
+    }
+
+    #[test]
+    fn rect_test() {
+        let mut pixels = vec![Gray8(0); 30];
+        let mut canvas = Canvas::new(6, 5, &mut pixels).unwrap();
+        canvas.rect(0, 0, 4, 4, ON);
+        canvas.fill_rect(3, 3, 3, 2, ON);
+        canvas.rect(5, 0, 1, 1, ON);
+        canvas.rect(5, 1, 0, 1, ON);
+        assert_eq!(render(&canvas), [
+            "####.#",
+            "#..#..",
+            "#..#..",
+            "######",
+            "...###",
+        ]);
+    }
+
+    #[test]
+    fn circle_test() {
+        let mut pixels = vec![Gray8(0); 49];
+        let mut canvas = Canvas::new(7, 7, &mut pixels).unwrap();
+        canvas.circle(3, 3, 3, ON);
+        assert_eq!(render(&canvas), [
+            "..###..",
+            ".#...#.",
+            "#.....#",
+            "#.....#",
+            "#.....#",
+            ".#...#.",
+            "..###..",
+        ]);
+        canvas.fill_circle(3, 3, 3, ON);
+        assert_eq!(render(&canvas), [
+            "..###..",
+            ".#####.",
+            "#######",
+            "#######",
+            "#######",
+            ".#####.",
+            "..###..",
+        ]);
+    }
+
+    #[test]
+    fn ellipse_test() {
+        let mut pixels = vec![Gray8(0); 45];
+        let mut canvas = Canvas::new(9, 5, &mut pixels).unwrap();
+        canvas.ellipse(4, 2, 4, 2, ON);
+        assert_eq!(render(&canvas), [
+            "..#####..",
+            ".#.....#.",
+            "#.......#",
+            ".#.....#.",
+            "..#####..",
+        ]);
+        canvas.fill(Gray8(0));
+        canvas.ellipse(4, 2, 3, 0, ON);
+        canvas.fill_ellipse(0, 0, 0, 1, ON);
+        assert_eq!(render(&canvas), [
+            "#........",
+            "#........",
+            ".#######.",
+            ".........",
+            ".........",
+        ]);
+    }
+
+    #[test]
+    fn polygon_test() {
+        let mut pixels = vec![Gray8(0); 36];
+        let mut canvas = Canvas::new(6, 6, &mut pixels).unwrap();
+        canvas.fill_polygon(&[(0, 0), (6, 0), (0, 6)], ON).unwrap();
+        assert_eq!(render(&canvas), [
+            "#####.",
+            "####..",
+            "###...",
+            "##....",
+            "#.....",
+            "......",
+        ]);
+
+        canvas.fill(Gray8(0));
+        canvas.polygon(&[(0, 0), (5, 0), (5, 5), (0, 5)], ON);
+        // even-odd hole
+        canvas.fill_polygon(&[(1, 1), (5, 1), (5, 5), (1, 5), (1, 1), (2, 2), (4, 2), (4, 4), (2, 4), (2, 2)], ON).unwrap();
+        assert_eq!(render(&canvas), [
+            "######",
+            "######",
+            "##..##",
+            "##..##",
+            "######",
+            "######",
+        ]);
+
+        let many = [(0, 0); MAX_POLYGON_VERTICES + 1];
+        assert!(canvas.fill_polygon(&many, ON).is_err());
+        canvas.fill_polygon(&[], ON).unwrap();
+        canvas.polygon(&[], ON);
+    }
+
+    #[test]
+    fn blit_test() {
+        let sprite_pixels = [ON, Gray8(0), ON, ON];
+        let sprite = Image::new(2, 2, &sprite_pixels).unwrap();
+        let mut pixels = vec![Gray8(0); 16];
+        let mut canvas = Canvas::new(4, 4, &mut pixels).unwrap();
+        canvas.blit(-1, -1, &sprite);
+        canvas.blit(1, 1, &sprite);
+        canvas.blit(3, 3, &sprite);
+        canvas.blit(10, 0, &sprite);
+        assert_eq!(render(&canvas), [
+            "#...",
+            ".#..",
+            ".##.",
+            "...#",
+        ]);
+    }
+
+    #[test]
+    fn mono_test() {
+        let mut pixels = vec![Mono(0); 4];
+        let mut canvas = Canvas::new(10, 2, &mut pixels).unwrap();
+        canvas.line(0, 0, 9, 0, true);
+        canvas.set_pixel(9, 1, true);
+        canvas.set_pixel(0, 0, false);
+        assert_eq!(canvas.pixel(9, 1), Some(true));
+        assert_eq!(canvas.pixel(10, 1), None);
+        assert_eq!(pixels, [Mono(0x7f), Mono(0xc0), Mono(0x00), Mono(0x40)]);
+    }
+}
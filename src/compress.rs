@@ -0,0 +1,356 @@
+use crate::winclient::Format;
+
+/// Codec of present payload. Encoders return `None` if output does not fit `dst`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None = 0,
+    /// PackBits style run-length, good for 1 byte pixels with flat areas
+    Rle = 1,
+    /// LZ4 block format, blocks are readable by any LZ4 block decoder
+    Lz = 2,
+}
+
+impl Compression {
+    /// Codec which suits pixels of `format` best
+    pub fn for_format(format: Format) -> Self {
+        match format {
+            Format::GS | Format::VGA | Format::MONO => Compression::Rle,
+            Format::ARGB | Format::RGB565 | Format::RGB888 | Format::RGBA | Format::PRGBA => Compression::Lz,
+        }
+    }
+
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Rle),
+            2 => Some(Compression::Lz),
+            _ => None
+        }
+    }
+
+    /// Encode `src` into `dst` and return count of bytes written
+    pub fn encode(self, src: &[u8], dst: &mut [u8]) -> Option<usize> {
+        match self {
+            Compression::None => {
+                dst.get_mut(..src.len())?.copy_from_slice(src);
+                Some(src.len())
+            },
+            Compression::Rle => rle_encode(src, dst),
+            Compression::Lz => lz_encode(src, dst),
+        }
+    }
+
+    /// Decode `src` into `dst` and return count of bytes written
+    pub fn decode(self, src: &[u8], dst: &mut [u8]) -> Option<usize> {
+        match self {
+            Compression::None => Compression::None.encode(src, dst),
+            Compression::Rle => rle_decode(src, dst),
+            Compression::Lz => lz_decode(src, dst),
+        }
+    }
+}
+
+/// Writes into fixed buffer failing on overflow
+struct Out<'a> {
+    buf: &'a mut [u8],
+    len: usize
+}
+
+impl<'a> Out<'a> {
+    #[inline]
+    fn push(&mut self, b: u8) -> Option<()> {
+        *self.buf.get_mut(self.len)? = b;
+        self.len += 1;
+        Some(())
+    }
+
+    #[inline]
+    fn extend(&mut self, b: &[u8]) -> Option<()> {
+        self.buf.get_mut(self.len..self.len + b.len())?.copy_from_slice(b);
+        self.len += b.len();
+        Some(())
+    }
+}
+
+const RLE_MIN_RUN: usize = 3;
+const RLE_MAX_RUN: usize = 130;
+const RLE_MAX_LITERALS: usize = 128;
+
+/// Control byte `n < 128` is followed by `n + 1` literal bytes,
+/// `n >= 128` by single byte repeated `n - 125` times
+pub fn rle_encode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut out = Out { buf: dst, len: 0 };
+    let mut literals = 0;
+    let mut i = 0;
+    while i < src.len() {
+        let run = src[i..].iter().take(RLE_MAX_RUN).take_while(|b| **b == src[i]).count();
+        if run >= RLE_MIN_RUN {
+            rle_flush_literals(&mut out, &src[i - literals..i])?;
+            literals = 0;
+            out.push((run + 125) as u8)?;
+            out.push(src[i])?;
+            i += run;
+        } else {
+            literals += 1;
+            i += 1;
+            if literals == RLE_MAX_LITERALS {
+                rle_flush_literals(&mut out, &src[i - literals..i])?;
+                literals = 0;
+            }
+        }
+    }
+    rle_flush_literals(&mut out, &src[i - literals..i])?;
+    Some(out.len)
+}
+
+fn rle_flush_literals(out: &mut Out, literals: &[u8]) -> Option<()> {
+    if !literals.is_empty() {
+        out.push((literals.len() - 1) as u8)?;
+        out.extend(literals)?;
+    }
+    Some(())
+}
+
+pub fn rle_decode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut out = Out { buf: dst, len: 0 };
+    let mut i = 0;
+    while i < src.len() {
+        let n = src[i] as usize;
+        if n < 128 {
+            out.extend(src.get(i + 1..i + 2 + n)?)?;
+            i += 2 + n;
+        } else {
+            let b = *src.get(i + 1)?;
+            for _ in 0..n - 125 {
+                out.push(b)?;
+            }
+            i += 2;
+        }
+    }
+    Some(out.len)
+}
+
+const LZ_MIN_MATCH: usize = 4;
+const LZ_MAX_OFFSET: usize = u16::MAX as usize;
+const LZ_HASH_BITS: u32 = 12;
+/// LZ4 end of block rules: last bytes are literals, last match starts this far before end
+const LZ_LAST_LITERALS: usize = 5;
+const LZ_MF_LIMIT: usize = 12;
+
+#[inline]
+fn lz_hash(src: &[u8], i: usize) -> usize {
+    let v = u32::from_le_bytes([src[i], src[i + 1], src[i + 2], src[i + 3]]);
+    (v.wrapping_mul(2654435761) >> (32 - LZ_HASH_BITS)) as usize
+}
+
+/// Length in 4 bits of token followed by 255 valued extension bytes
+fn lz_push_len(out: &mut Out, len: usize) -> Option<()> {
+    if len >= 15 {
+        let mut rest = len - 15;
+        while rest >= 255 {
+            out.push(255)?;
+            rest -= 255;
+        }
+        out.push(rest as u8)?;
+    }
+    Some(())
+}
+
+fn lz_push_sequence(out: &mut Out, literals: &[u8], lz_match: Option<(usize, usize)>) -> Option<()> {
+    let match_len = lz_match.map_or(0, |(_, len)| len - LZ_MIN_MATCH);
+    out.push(((literals.len().min(15) as u8) << 4) | match_len.min(15) as u8)?;
+    lz_push_len(out, literals.len())?;
+    out.extend(literals)?;
+    if let Some((offset, _)) = lz_match {
+        out.extend(&(offset as u16).to_le_bytes())?;
+        lz_push_len(out, match_len)?;
+    }
+    Some(())
+}
+
+/// Greedy single pass encoder. Stream ends with sequence of at least `LZ_LAST_LITERALS` literals
+/// (or all of short input) as LZ4 requires
+pub fn lz_encode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut out = Out { buf: dst, len: 0 };
+    // position + 1 of last occurrence of hashed 4 bytes (0 is empty)
+    let mut table = [0u32; 1 << LZ_HASH_BITS];
+    let mut anchor = 0;
+    let mut i = 0;
+    while i + LZ_MF_LIMIT <= src.len() {
+        let h = lz_hash(src, i);
+        let candidate = table[h] as usize;
+        table[h] = (i + 1) as u32;
+
+        if candidate > 0 && i - (candidate - 1) <= LZ_MAX_OFFSET
+            && src[candidate - 1..candidate - 1 + LZ_MIN_MATCH] == src[i..i + LZ_MIN_MATCH] {
+            let start = candidate - 1;
+            let len = LZ_MIN_MATCH + src[i + LZ_MIN_MATCH..].iter()
+                .zip(&src[start + LZ_MIN_MATCH..])
+                .take(src.len() - LZ_LAST_LITERALS - i - LZ_MIN_MATCH)
+                .take_while(|(a, b)| a == b)
+                .count();
+            lz_push_sequence(&mut out, &src[anchor..i], Some((i - start, len)))?;
+            i += len;
+            anchor = i;
+        } else {
+            i += 1;
+        }
+    }
+    lz_push_sequence(&mut out, &src[anchor..], None)?;
+    Some(out.len)
+}
+
+fn lz_read_len(src: &[u8], i: &mut usize, len: usize) -> Option<usize> {
+    let mut len = len;
+    if len == 15 {
+        loop {
+            let b = *src.get(*i)?;
+            *i += 1;
+            len += b as usize;
+            if b != 255 {
+                break
+            }
+        }
+    }
+    Some(len)
+}
+
+pub fn lz_decode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut out = Out { buf: dst, len: 0 };
+    let mut i = 0;
+    while i < src.len() {
+        let token = src[i];
+        i += 1;
+
+        let literals = lz_read_len(src, &mut i, (token >> 4) as usize)?;
+        out.extend(src.get(i..i + literals)?)?;
+        i += literals;
+        if i == src.len() {
+            break
+        }
+
+        let offset = u16::from_le_bytes([*src.get(i)?, *src.get(i + 1)?]) as usize;
+        i += 2;
+        let len = lz_read_len(src, &mut i, (token & 0xf) as usize)? + LZ_MIN_MATCH;
+        if offset == 0 || offset > out.len {
+            return None
+        }
+        // match may overlap bytes it produces
+        for _ in 0..len {
+            out.push(out.buf[out.len - offset])?;
+        }
+    }
+    Some(out.len)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{vec, vec::Vec};
+
+    use super::*;
+
+    fn samples() -> Vec<Vec<u8>> {
+        let mut noise = Vec::new();
+        let mut x: u32 = 12345;
+        for _ in 0..5000 {
+            x = x.wrapping_mul(1103515245).wrapping_add(12345);
+            noise.push((x >> 16) as u8);
+        }
+        let argb: Vec<u8> = (0..64 * 64u32)
+            .flat_map(|i| if (i / 64 + i % 64) % 16 < 8 { 0xff20_4080u32 } else { 0xff00_0000 }.to_ne_bytes())
+            .collect();
+        vec![
+            Vec::new(),
+            vec![7],
+            vec![1, 2],
+            vec![0; 10000],
+            (0..1000).map(|i| (i / 7) as u8).collect(),
+            (0..1000).map(|i| i as u8).collect(),
+            (0..300).map(|i| if i % 3 == 0 { 1 } else { 2 }).collect(),
+            noise,
+            argb,
+        ]
+    }
+
+    fn round_trip(compression: Compression) {
+        for sample in samples() {
+            let mut encoded = vec![0; sample.len() * 2 + 16];
+            let encoded_len = compression.encode(&sample, &mut encoded).unwrap();
+            let mut decoded = vec![0; sample.len()];
+            assert_eq!(compression.decode(&encoded[..encoded_len], &mut decoded), Some(sample.len()));
+            assert_eq!(decoded, sample);
+        }
+    }
+
+    #[test]
+    fn rle_round_trip_test() {
+        round_trip(Compression::Rle);
+    }
+
+    #[test]
+    fn lz_round_trip_test() {
+        round_trip(Compression::Lz);
+    }
+
+    #[test]
+    fn lz_end_of_block_test() {
+        for sample in samples() {
+            let mut encoded = vec![0; sample.len() * 2 + 16];
+            let encoded_len = lz_encode(&sample, &mut encoded).unwrap();
+            let encoded = &encoded[..encoded_len];
+
+            let mut i = 0;
+            let mut decoded_len = 0;
+            let mut last_literals = 0;
+            while i < encoded.len() {
+                let token = encoded[i];
+                i += 1;
+                last_literals = lz_read_len(encoded, &mut i, (token >> 4) as usize).unwrap();
+                i += last_literals;
+                decoded_len += last_literals;
+                if i == encoded.len() {
+                    break
+                }
+                i += 2;
+                let len = lz_read_len(encoded, &mut i, (token & 0xf) as usize).unwrap() + LZ_MIN_MATCH;
+                assert!(decoded_len + LZ_MF_LIMIT <= sample.len());
+                decoded_len += len;
+                assert!(decoded_len + LZ_LAST_LITERALS <= sample.len());
+            }
+            assert_eq!(decoded_len, sample.len());
+            assert!(last_literals >= LZ_LAST_LITERALS.min(sample.len()));
+        }
+    }
+
+    #[test]
+    fn ratio_test() {
+        let flat = vec![3u8; 4096];
+        let mut encoded = vec![0; 4096];
+        assert!(Compression::Rle.encode(&flat, &mut encoded).unwrap() < 100);
+        assert!(Compression::Lz.encode(&flat, &mut encoded).unwrap() < 100);
+    }
+
+    #[test]
+    fn overflow_test() {
+        let data: Vec<u8> = (0..100).collect();
+        let mut small = [0; 50];
+        assert_eq!(Compression::Rle.encode(&data, &mut small), None);
+        assert_eq!(Compression::Lz.encode(&data, &mut small), None);
+        assert_eq!(Compression::None.encode(&data, &mut small), None);
+
+        let mut encoded = [0; 200];
+        let len = Compression::Lz.encode(&data, &mut encoded).unwrap();
+        assert_eq!(Compression::Lz.decode(&encoded[..len], &mut small), None);
+    }
+
+    #[test]
+    fn corrupt_test() {
+        let mut dst = [0; 64];
+        // offset before start of output
+        assert_eq!(lz_decode(&[0x10, 1, 5, 0], &mut dst), None);
+        // truncated literals
+        assert_eq!(rle_decode(&[5, 1, 2], &mut dst), None);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::{
+    pixel::{self, Argb8888, Gray8, Mono, Pixel, PremulRgba8888, Rgb565, Rgb888, Rgba8888, Vga8},
+    winclient::PALETTE_LEN
+};
+
+/// Default palette of `Format::VGA` frames as `0xAARRGGBB`, the same server uses
+pub const VGA_PALETTE: [u32; PALETTE_LEN] = [
+    0xff000000, 0xff0002aa, 0xff14aa00, 0xff00aaaa, 0xffaa0003, 0xffaa00aa, 0xffaa5500, 0xffaaaaaa,
+    0xff555555, 0xff5555ff, 0xff55ff55, 0xff55ffff, 0xffff5555, 0xfffd55ff, 0xffffff55, 0xffffffff,
+    0xff000000, 0xff101010, 0xff202020, 0xff353535, 0xff454545, 0xff555555, 0xff656565, 0xff757575,
+    0xff8a8a8a, 0xff9a9a9a, 0xffaaaaaa, 0xffbababa, 0xffcacaca, 0xffdfdfdf, 0xffefefef, 0xffffffff,
+    0xff0004ff, 0xff4104ff, 0xff8203ff, 0xffbe02ff, 0xfffd00ff, 0xfffe00be, 0xffff0082, 0xffff0041,
+    0xffff0008, 0xffff4105, 0xffff8200, 0xffffbe00, 0xffffff00, 0xffbeff00, 0xff82ff00, 0xff41ff01,
+    0xff24ff00, 0xff22ff42, 0xff1dff82, 0xff12ffbe, 0xff00ffff, 0xff00beff, 0xff0182ff, 0xff0041ff,
+    0xff8282ff, 0xff9e82ff, 0xffbe82ff, 0xffdf82ff, 0xfffd82ff, 0xfffe82df, 0xffff82be, 0xffff829e,
+    0xffff8282, 0xffff9e82, 0xffffbe82, 0xffffdf82, 0xffffff82, 0xffdfff82, 0xffbeff82, 0xff9eff82,
+    0xff82ff82, 0xff82ff9e, 0xff82ffbe, 0xff82ffdf, 0xff82ffff, 0xff82dfff, 0xff82beff, 0xff829eff,
+    0xffbabaff, 0xffcabaff, 0xffdfbaff, 0xffefbaff, 0xfffebaff, 0xfffebaef, 0xffffbadf, 0xffffbaca,
+    0xffffbaba, 0xffffcaba, 0xffffdfba, 0xffffefba, 0xffffffba, 0xffefffba, 0xffdfffba, 0xffcaffbb,
+    0xffbaffba, 0xffbaffca, 0xffbaffdf, 0xffbaffef, 0xffbaffff, 0xffbaefff, 0xffbadfff, 0xffbacaff,
+    0xff010171, 0xff1c0171, 0xff390171, 0xff550071, 0xff710071, 0xff710055, 0xff710039, 0xff71001c,
+    0xff710001, 0xff711c01, 0xff713900, 0xff715500, 0xff717100, 0xff557100, 0xff397100, 0xff1c7100,
+    0xff097100, 0xff09711c, 0xff067139, 0xff037155, 0xff007171, 0xff005571, 0xff003971, 0xff001c71,
+    0xff393971, 0xff453971, 0xff553971, 0xff613971, 0xff713971, 0xff713961, 0xff713955, 0xff713945,
+    0xff713939, 0xff714539, 0xff715539, 0xff716139, 0xff717139, 0xff617139, 0xff557139, 0xff45713a,
+    0xff397139, 0xff397145, 0xff397155, 0xff397161, 0xff397171, 0xff396171, 0xff395571, 0xff394572,
+    0xff515171, 0xff595171, 0xff615171, 0xff695171, 0xff715171, 0xff715169, 0xff715161, 0xff715159,
+    0xff715151, 0xff715951, 0xff716151, 0xff716951, 0xff717151, 0xff697151, 0xff617151, 0xff597151,
+    0xff517151, 0xff51715a, 0xff517161, 0xff517169, 0xff517171, 0xff516971, 0xff516171, 0xff515971,
+    0xff000042, 0xff110041, 0xff200041, 0xff310041, 0xff410041, 0xff410032, 0xff410020, 0xff410010,
+    0xff410000, 0xff411000, 0xff412000, 0xff413100, 0xff414100, 0xff314100, 0xff204100, 0xff104100,
+    0xff034100, 0xff034110, 0xff024120, 0xff014131, 0xff004141, 0xff003141, 0xff002041, 0xff001041,
+    0xff202041, 0xff282041, 0xff312041, 0xff392041, 0xff412041, 0xff412039, 0xff412031, 0xff412028,
+    0xff412020, 0xff412820, 0xff413120, 0xff413921, 0xff414120, 0xff394120, 0xff314120, 0xff284120,
+    0xff204120, 0xff204128, 0xff204131, 0xff204139, 0xff204141, 0xff203941, 0xff203141, 0xff202841,
+    0xff2d2d41, 0xff312d41, 0xff352d41, 0xff3d2d41, 0xff412d41, 0xff412d3d, 0xff412d35, 0xff412d31,
+    0xff412d2d, 0xff41312d, 0xff41352d, 0xff413d2d, 0xff41412d, 0xff3d412d, 0xff35412d, 0xff31412d,
+    0xff2d412d, 0xff2d4131, 0xff2d4135, 0xff2d413d, 0xff2d4141, 0xff2d3d41, 0xff2d3541, 0xff2d3141,
+    0xff000000, 0xff000000, 0xff000000, 0xff000000, 0xff000000, 0xff000000, 0xff000000, 0xff000000,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    None,
+    /// 4x4 Bayer matrix, turns quantization error of low depth formats into fine pattern
+    Ordered,
+}
+
+#[derive(Debug)]
+pub enum ConvertError {
+    /// Source or destination does not hold `w` x `h` pixels
+    SizeMismatch,
+}
+
+/// Pixel readable and writable as straight alpha RGBA. Opaque formats drop alpha on write
+pub trait Convert: Pixel {
+    /// Largest difference between neighbour representable channel values, ordered dithering spreads colors by it
+    const STEP: u8;
+
+    /// Color of pixel `x` of `row`
+    fn get(row: &[Self], x: usize) -> Rgba8888;
+
+    /// Store pixel `x` of `row` as the closest representable color
+    fn set(row: &mut [Self], x: usize, c: Rgba8888);
+}
+
+#[inline]
+const fn opaque(r: u8, g: u8, b: u8) -> Rgba8888 {
+    Rgba8888 { r, g, b, a: 255 }
+}
+
+#[inline]
+const fn from_argb(v: u32) -> Rgba8888 {
+    Rgba8888 { r: (v >> 16) as u8, g: (v >> 8) as u8, b: v as u8, a: (v >> 24) as u8 }
+}
+
+/// Rec. 601 luma
+#[inline]
+fn luma(c: Rgba8888) -> u8 {
+    ((c.r as u32 * 77 + c.g as u32 * 150 + c.b as u32 * 29 + 128) >> 8) as u8
+}
+
+impl Convert for Gray8 {
+    const STEP: u8 = 1;
+
+    #[inline]
+    fn get(row: &[Self], x: usize) -> Rgba8888 {
+        opaque(row[x].0, row[x].0, row[x].0)
+    }
+
+    #[inline]
+    fn set(row: &mut [Self], x: usize, c: Rgba8888) {
+        row[x] = Gray8(luma(c));
+    }
+}
+
+impl Convert for Argb8888 {
+    const STEP: u8 = 1;
+
+    #[inline]
+    fn get(row: &[Self], x: usize) -> Rgba8888 {
+        from_argb(row[x].0)
+    }
+
+    #[inline]
+    fn set(row: &mut [Self], x: usize, c: Rgba8888) {
+        row[x] = Argb8888((c.a as u32) << 24 | (c.r as u32) << 16 | (c.g as u32) << 8 | c.b as u32);
+    }
+}
+
+/// Entry of `palette` of `0xAARRGGBB` colors closest to `c` (alpha is ignored)
+pub fn nearest_in(c: Rgba8888, palette: &[u32; PALETTE_LEN]) -> Vga8 {
+    let distance = |v: &u32| {
+        let p = from_argb(*v);
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(p.r, c.r) + d(p.g, c.g) + d(p.b, c.b)
+    };
+    let (i, _) = palette.iter()
+        .enumerate()
+        .min_by_key(|(_, v)| distance(v))
+        .unwrap();
+    Vga8(i as u8)
+}
+
+/// Default palette entry closest to `c` (alpha is ignored)
+#[inline]
+pub fn nearest_vga(c: Rgba8888) -> Vga8 {
+    nearest_in(c, &VGA_PALETTE)
+}
+
+/// Pixel `x` of `row` shown with `palette`
+#[inline]
+fn palette_color(row: &[Vga8], x: usize, palette: &[u32; PALETTE_LEN]) -> Rgba8888 {
+    from_argb(palette[row[x].0 as usize])
+}
+
+/// Frames with default palette, `convert_to_vga` and `convert_from_vga` take palette changed by `Client::set_palette`
+impl Convert for Vga8 {
+    /// Typical distance between neighbour palette colors
+    const STEP: u8 = 48;
+
+    #[inline]
+    fn get(row: &[Self], x: usize) -> Rgba8888 {
+        palette_color(row, x, &VGA_PALETTE)
+    }
+
+    #[inline]
+    fn set(row: &mut [Self], x: usize, c: Rgba8888) {
+        row[x] = nearest_vga(c);
+    }
+}
+
+impl Convert for Rgb565 {
+    const STEP: u8 = 8;
+
+    #[inline]
+    fn get(row: &[Self], x: usize) -> Rgba8888 {
+        let v = row[x].to_u16();
+        let (r, g, b) = ((v >> 11) as u8, (v >> 5) as u8 & 0x3f, v as u8 & 0x1f);
+        // replicate high bits so full intensity stays 255
+        opaque(r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2)
+    }
+
+    #[inline]
+    fn set(row: &mut [Self], x: usize, c: Rgba8888) {
+        row[x] = Rgb565::new(c.r, c.g, c.b);
+    }
+}
+
+impl Convert for Rgb888 {
+    const STEP: u8 = 1;
+
+    #[inline]
+    fn get(row: &[Self], x: usize) -> Rgba8888 {
+        opaque(row[x].r, row[x].g, row[x].b)
+    }
+
+    #[inline]
+    fn set(row: &mut [Self], x: usize, c: Rgba8888) {
+        row[x] = Rgb888 { r: c.r, g: c.g, b: c.b };
+    }
+}
+
+impl Convert for Rgba8888 {
+    const STEP: u8 = 1;
+
+    #[inline]
+    fn get(row: &[Self], x: usize) -> Rgba8888 {
+        row[x]
+    }
+
+    #[inline]
+    fn set(row: &mut [Self], x: usize, c: Rgba8888) {
+        row[x] = c;
+    }
+}
+
+impl Convert for PremulRgba8888 {
+    const STEP: u8 = 1;
+
+    #[inline]
+    fn get(row: &[Self], x: usize) -> Rgba8888 {
+        let p = row[x];
+        if p.a == 0 {
+            return Rgba8888::default()
+        }
+        let div = |c: u8| ((c as u32 * 255 + p.a as u32 / 2) / p.a as u32).min(255) as u8;
+        Rgba8888 { r: div(p.r), g: div(p.g), b: div(p.b), a: p.a }
+    }
+
+    #[inline]
+    fn set(row: &mut [Self], x: usize, c: Rgba8888) {
+        row[x] = c.into();
+    }
+}
+
+impl Convert for Mono {
+    const STEP: u8 = 255;
+
+    #[inline]
+    fn get(row: &[Self], x: usize) -> Rgba8888 {
+        let v = if row[x / 8].get((x % 8) as u8) { 255 } else { 0 };
+        opaque(v, v, v)
+    }
+
+    #[inline]
+    fn set(row: &mut [Self], x: usize, c: Rgba8888) {
+        row[x / 8].set((x % 8) as u8, luma(c) >= 128);
+    }
+}
+
+const BAYER: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Shift color by threshold of pixel `x`, `y` in range of `-step / 2..step / 2`
+#[inline]
+fn dither(c: Rgba8888, x: usize, y: usize, step: u8) -> Rgba8888 {
+    let offset = (BAYER[y % 4][x % 4] as i32 * 2 + 1) * step as i32 / 32 - step as i32 / 2;
+    let shift = |v: u8| (v as i32 + offset).clamp(0, 255) as u8;
+    Rgba8888 { r: shift(c.r), g: shift(c.g), b: shift(c.b), a: c.a }
+}
+
+/// Convert `w` x `h` surface `src` into `dst`
+#[inline]
+pub fn convert<S: Convert, D: Convert>(w: u16, h: u16, src: &[S], dst: &mut [D], dither_mode: Dither) -> Result<(), ConvertError> {
+    convert_with(w, h, src, dst, dither_mode, S::get, D::set)
+}
+
+/// `convert` into `Format::VGA` frame shown with `palette` of `0xAARRGGBB` colors,
+/// e.g. copy of `VGA_PALETTE` with changes sent by `Client::set_palette`
+pub fn convert_to_vga<S: Convert>(w: u16, h: u16, src: &[S], dst: &mut [Vga8], dither_mode: Dither, palette: &[u32; PALETTE_LEN]) -> Result<(), ConvertError> {
+    convert_with(w, h, src, dst, dither_mode, S::get, |row, x, c| row[x] = nearest_in(c, palette))
+}
+
+/// `convert` of `Format::VGA` frame shown with `palette` of `0xAARRGGBB` colors
+pub fn convert_from_vga<D: Convert>(w: u16, h: u16, src: &[Vga8], dst: &mut [D], dither_mode: Dither, palette: &[u32; PALETTE_LEN]) -> Result<(), ConvertError> {
+    convert_with(w, h, src, dst, dither_mode, |row, x| palette_color(row, x, palette), D::set)
+}
+
+/// `convert` reading and writing pixels through `get` and `set`
+fn convert_with<S: Pixel, D: Convert>(
+    w: u16,
+    h: u16,
+    src: &[S],
+    dst: &mut [D],
+    dither_mode: Dither,
+    get: impl Fn(&[S], usize) -> Rgba8888,
+    set: impl Fn(&mut [D], usize, Rgba8888)
+) -> Result<(), ConvertError> {
+    if src.len() != pixel::surface_len::<S>(w, h) || dst.len() != pixel::surface_len::<D>(w, h) {
+        return Err(ConvertError::SizeMismatch)
+    }
+    if w == 0 {
+        return Ok(())
+    }
+
+    let rows = src.chunks_exact(pixel::row_len::<S>(w)).zip(dst.chunks_exact_mut(pixel::row_len::<D>(w)));
+    for (y, (src_row, dst_row)) in rows.enumerate() {
+        for x in 0..w as usize {
+            let c = get(src_row, x);
+            let c = if dither_mode == Dither::Ordered && D::STEP > 1 { dither(c, x, y, D::STEP) } else { c };
+            set(dst_row, x, c);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec;
+
+    use super::*;
+
+    fn one<S: Convert, D: Convert + Default>(p: S) -> D {
+        let mut dst = [D::default(); 1];
+        convert(1, 1, &[p], &mut dst, Dither::None).unwrap();
+        dst[0]
+    }
+
+    #[test]
+    fn known_values_test() {
+        assert_eq!(one::<_, Argb8888>(Gray8(0x80)), Argb8888(0xff80_8080));
+        assert_eq!(one::<_, Gray8>(Argb8888(0xffff_0000)), Gray8(77));
+        assert_eq!(one::<_, Gray8>(Rgb888 { r: 255, g: 255, b: 255 }), Gray8(255));
+        assert_eq!(one::<_, Rgba8888>(Argb8888(0x8010_2030)), Rgba8888 { r: 0x10, g: 0x20, b: 0x30, a: 0x80 });
+
+        assert_eq!(one::<_, Rgb565>(Rgb888 { r: 255, g: 255, b: 255 }), Rgb565::from_u16(0xffff));
+        assert_eq!(one::<_, Rgb888>(Rgb565::from_u16(0xf800)), Rgb888 { r: 255, g: 0, b: 0 });
+        assert_eq!(one::<_, Rgb888>(Rgb565::from_u16(0x07e0)), Rgb888 { r: 0, g: 255, b: 0 });
+
+        assert_eq!(one::<_, PremulRgba8888>(Rgba8888 { r: 255, g: 100, b: 0, a: 128 }), PremulRgba8888 { r: 128, g: 50, b: 0, a: 128 });
+        assert_eq!(one::<_, Rgba8888>(PremulRgba8888 { r: 128, g: 50, b: 0, a: 128 }), Rgba8888 { r: 255, g: 100, b: 0, a: 128 });
+        assert_eq!(one::<_, Rgba8888>(PremulRgba8888::default()), Rgba8888::default());
+    }
+
+    #[test]
+    fn vga_test() {
+        assert_eq!(one::<_, Rgb888>(Vga8(1)), Rgb888 { r: 0x00, g: 0x02, b: 0xaa });
+        assert_eq!(one::<_, Vga8>(Rgb888 { r: 0xaa, g: 0, b: 0xaa }), Vga8(5));
+        assert_eq!(one::<_, Vga8>(Rgb888 { r: 0, g: 0, b: 0 }), Vga8(0));
+        // the same white is in 16 color part and in gray ramp, first wins
+        assert_eq!(one::<_, Vga8>(Gray8(255)), Vga8(15));
+        assert_eq!(one::<_, Vga8>(Rgb888 { r: 0xa8, g: 0x05, b: 0x02 }), Vga8(4));
+        // every palette color maps to itself or its duplicate
+        for i in 0..=255u8 {
+            let c: Rgb888 = one(Vga8(i));
+            assert_eq!(one::<_, Rgb888>(one::<_, Vga8>(c)), c);
+        }
+    }
+
+    #[test]
+    fn custom_palette_test() {
+        // first color of default palette replaced by orange
+        let mut palette = VGA_PALETTE;
+        palette[0] = 0xffff_8000;
+        let orange = Rgb888 { r: 0xff, g: 0x80, b: 0 };
+
+        let mut vga = [Vga8(0); 2];
+        convert_to_vga(2, 1, &[orange, Rgb888::default()], &mut vga, Dither::None, &palette).unwrap();
+        assert_eq!(vga[0], Vga8(0));
+        // black is still in gray ramp
+        assert_eq!(from_argb(palette[vga[1].0 as usize]), opaque(0, 0, 0));
+        // default palette has no orange at 0
+        assert_ne!(one::<_, Vga8>(orange), Vga8(0));
+
+        let mut rgb = [Rgb888::default(); 2];
+        convert_from_vga(2, 1, &[Vga8(0), Vga8(1)], &mut rgb, Dither::None, &palette).unwrap();
+        assert_eq!(rgb, [orange, one(Vga8(1))]);
+
+        // default palette gives the same as Convert impl
+        let src: [Gray8; 4] = [Gray8(0), Gray8(90), Gray8(180), Gray8(255)];
+        let (mut a, mut b) = ([Vga8(0); 4], [Vga8(0); 4]);
+        convert(4, 1, &src, &mut a, Dither::Ordered).unwrap();
+        convert_to_vga(4, 1, &src, &mut b, Dither::Ordered, &VGA_PALETTE).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn mono_test() {
+        let src: [Gray8; 20] = core::array::from_fn(|i| Gray8(if i % 3 == 0 { 200 } else { 10 }));
+        let mut dst = [Mono(0); 4];
+        convert(10, 2, &src, &mut dst, Dither::None).unwrap();
+        // rows are padded to whole bytes
+        assert_eq!(dst, [Mono(0b1001_0010), Mono(0b0100_0000), Mono(0b0010_0100), Mono(0b1000_0000)]);
+
+        let mut back = [Gray8(0); 20];
+        convert(10, 2, &dst, &mut back, Dither::None).unwrap();
+        assert_eq!(back.map(|p| p.0 > 0), src.map(|p| p.0 > 128));
+    }
+
+    #[test]
+    fn dither_test() {
+        let src = [Gray8(128); 16];
+        let mut dst = [Mono(0); 4];
+        convert(4, 4, &src, &mut dst, Dither::None).unwrap();
+        assert!(dst.iter().all(|m| m.0 == 0xf0));
+
+        convert(4, 4, &src, &mut dst, Dither::Ordered).unwrap();
+        let white: u32 = dst.iter().map(|m| m.0.count_ones()).sum();
+        assert_eq!(white, 8);
+        // checkerboard like pattern, no two rows alike
+        assert_ne!(dst[0], dst[1]);
+
+        // lossless targets are not dithered
+        let mut rgb = [Rgb888::default(); 16];
+        convert(4, 4, &src, &mut rgb, Dither::Ordered).unwrap();
+        assert!(rgb.iter().all(|p| *p == Rgb888 { r: 128, g: 128, b: 128 }));
+    }
+
+    #[test]
+    fn size_mismatch_test() {
+        let src = vec![Gray8(0); 12];
+        let mut dst = vec![Argb8888(0); 12];
+        assert!(convert(4, 3, &src, &mut dst, Dither::None).is_ok());
+        assert!(matches!(convert(4, 4, &src, &mut dst, Dither::None), Err(ConvertError::SizeMismatch)));
+        assert!(matches!(convert(4, 3, &src, &mut dst[..11], Dither::None), Err(ConvertError::SizeMismatch)));
+        assert!(convert::<Gray8, Mono>(0, 5, &[], &mut [], Dither::None).is_ok());
+    }
+}
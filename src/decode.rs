@@ -0,0 +1,586 @@
+use crate::{
+    convert::Convert,
+    pixel::{self, Rgba8888},
+    read::{read_be_u32, read_le_u16, read_le_u32}
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Not BMP, binary PPM/PGM or QOI
+    UnknownFormat,
+    /// Truncated or inconsistent data
+    Malformed,
+    /// Valid file this decoder does not handle, e.g. RLE compressed BMP or ASCII PPM
+    Unsupported,
+    /// Width or height does not fit `u16`
+    TooLarge,
+    /// Destination does not hold exactly `w` x `h` pixels
+    SizeMismatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Bmp,
+    /// Binary PGM or PPM
+    Pnm,
+    Qoi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub w: u16,
+    pub h: u16
+}
+
+/// Format and size of image in `src`, e.g. to pick destination buffer
+pub fn info(src: &[u8]) -> Result<ImageInfo, DecodeError> {
+    parse(src).map(|parsed| parsed.info())
+}
+
+/// Decode image into `dst` holding exactly `w` x `h` pixels of `info(src)`, ready for `Client::present`.
+/// Colors are converted like `convert::convert` without dithering.
+/// On error `dst` may be partially written
+pub fn decode<P: Convert>(src: &[u8], dst: &mut [P]) -> Result<ImageInfo, DecodeError> {
+    let parsed = parse(src)?;
+    let info = parsed.info();
+    if dst.len() != pixel::surface_len::<P>(info.w, info.h) {
+        return Err(DecodeError::SizeMismatch)
+    }
+    let mut target = Target { row_len: pixel::row_len::<P>(info.w), dst };
+    match parsed {
+        Parsed::Bmp(bmp) => bmp.decode(&mut target),
+        Parsed::Pnm(pnm) => pnm.decode(&mut target),
+        Parsed::Qoi(qoi) => qoi.decode(&mut target),
+    }?;
+    Ok(info)
+}
+
+struct Target<'a, P> {
+    dst: &'a mut [P],
+    row_len: usize
+}
+
+impl<P: Convert> Target<'_, P> {
+    #[inline]
+    fn put(&mut self, x: usize, y: usize, c: Rgba8888) {
+        P::set(&mut self.dst[y * self.row_len..], x, c);
+    }
+}
+
+enum Parsed<'a> {
+    Bmp(Bmp<'a>),
+    Pnm(Pnm<'a>),
+    Qoi(Qoi<'a>),
+}
+
+impl Parsed<'_> {
+    fn info(&self) -> ImageInfo {
+        let (format, w, h) = match self {
+            Parsed::Bmp(bmp) => (ImageFormat::Bmp, bmp.w, bmp.h),
+            Parsed::Pnm(pnm) => (ImageFormat::Pnm, pnm.w, pnm.h),
+            Parsed::Qoi(qoi) => (ImageFormat::Qoi, qoi.w, qoi.h),
+        };
+        ImageInfo { format, w, h }
+    }
+}
+
+fn parse(src: &[u8]) -> Result<Parsed<'_>, DecodeError> {
+    if src.starts_with(b"BM") {
+        Bmp::parse(src).map(Parsed::Bmp)
+    } else if src.starts_with(b"qoif") {
+        Qoi::parse(src).map(Parsed::Qoi)
+    } else if src.first() == Some(&b'P') {
+        Pnm::parse(src).map(Parsed::Pnm)
+    } else {
+        Err(DecodeError::UnknownFormat)
+    }
+}
+
+fn check_size(w: u32, h: u32) -> Result<(u16, u16), DecodeError> {
+    if w == 0 || h == 0 {
+        return Err(DecodeError::Malformed)
+    }
+    match (u16::try_from(w), u16::try_from(h)) {
+        (Ok(w), Ok(h)) => Ok((w, h)),
+        _ => Err(DecodeError::TooLarge)
+    }
+}
+
+/// `v` of `0..=max` scaled to `0..=255`
+#[inline]
+fn scale(v: u32, max: u32) -> u8 {
+    ((v as u64 * 255 + max as u64 / 2) / max as u64) as u8
+}
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+const BMP_CORE_HEADER: usize = 12;
+const BMP_INFO_HEADER: usize = 40;
+
+/// Uncompressed Windows bitmap with palette or bit masks
+struct Bmp<'a> {
+    w: u16,
+    h: u16,
+    bpp: u16,
+    top_down: bool,
+    /// Red, green, blue and alpha mask of 16 and 32 bit pixels, zero alpha mask means opaque
+    masks: [u32; 4],
+    /// BGR or BGRx entries
+    palette: &'a [u8],
+    palette_entry: usize,
+    stride: usize,
+    data: &'a [u8]
+}
+
+impl<'a> Bmp<'a> {
+    fn parse(src: &'a [u8]) -> Result<Self, DecodeError> {
+        let malformed = DecodeError::Malformed;
+        let mut file = src.get(10..).ok_or(malformed)?;
+        let offset = read_le_u32(&mut file).ok_or(malformed)? as usize;
+        let header_size = read_le_u32(&mut file).ok_or(malformed)? as usize;
+        let mut header = file.get(..header_size.checked_sub(4).ok_or(malformed)?).ok_or(malformed)?;
+
+        let (w, h, bpp, compression, colors) = if header_size == BMP_CORE_HEADER {
+            let w = read_le_u16(&mut header).ok_or(malformed)? as i64;
+            let h = read_le_u16(&mut header).ok_or(malformed)? as i64;
+            read_le_u16(&mut header).ok_or(malformed)?;
+            (w, h, read_le_u16(&mut header).ok_or(malformed)?, BI_RGB, 0)
+        } else if header_size >= BMP_INFO_HEADER {
+            let w = read_le_u32(&mut header).ok_or(malformed)? as i32 as i64;
+            let h = read_le_u32(&mut header).ok_or(malformed)? as i32 as i64;
+            read_le_u16(&mut header).ok_or(malformed)?;
+            let bpp = read_le_u16(&mut header).ok_or(malformed)?;
+            let compression = read_le_u32(&mut header).ok_or(malformed)?;
+            let mut rest = header.get(12..).ok_or(malformed)?;
+            (w, h, bpp, compression, read_le_u32(&mut rest).ok_or(malformed)?)
+        } else {
+            return Err(malformed)
+        };
+
+        if w < 0 {
+            return Err(malformed)
+        }
+        let top_down = h < 0;
+        let (w, h) = check_size(w.min(u32::MAX as i64) as u32, h.unsigned_abs().min(u32::MAX as u64) as u32)?;
+        if !matches!(bpp, 1 | 4 | 8 | 16 | 24 | 32) {
+            return Err(malformed)
+        }
+
+        // masks follow 40 byte header or are part of the newer ones
+        let mut masks_at = src.get(14 + BMP_INFO_HEADER..).unwrap_or(&[]);
+        let mut mask = || read_le_u32(&mut masks_at).ok_or(malformed);
+        let masks = match (bpp, compression) {
+            (1 | 4 | 8 | 24, BI_RGB) => [0; 4],
+            (16, BI_RGB) => [0x7c00, 0x03e0, 0x001f, 0],
+            (32, BI_RGB) => [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0],
+            (16 | 32, BI_BITFIELDS | BI_ALPHABITFIELDS) => {
+                let rgb = [mask()?, mask()?, mask()?];
+                let alpha = if compression == BI_ALPHABITFIELDS || header_size >= 56 { mask()? } else { 0 };
+                [rgb[0], rgb[1], rgb[2], alpha]
+            },
+            (_, BI_RGB | BI_BITFIELDS | BI_ALPHABITFIELDS) => return Err(malformed),
+            _ => return Err(DecodeError::Unsupported)
+        };
+
+        let (palette, palette_entry) = if bpp <= 8 {
+            let entry = if header_size == BMP_CORE_HEADER { 3 } else { 4 };
+            let count = match colors {
+                0 => 1 << bpp,
+                n if n <= 1 << bpp => n as usize,
+                _ => return Err(malformed),
+            };
+            let start = 14 + header_size;
+            (src.get(start..start + count * entry).ok_or(malformed)?, entry)
+        } else {
+            (&[][..], 0)
+        };
+
+        let row_bytes = (w as usize * bpp as usize).div_ceil(8);
+        let stride = row_bytes.div_ceil(4) * 4;
+        // padding of the last row may be cut
+        let data = src.get(offset..).ok_or(malformed)?;
+        let len = stride.checked_mul(h as usize - 1).and_then(|v| v.checked_add(row_bytes));
+        if len.is_none_or(|len| data.len() < len) {
+            return Err(malformed)
+        }
+        Ok(Self { w, h, bpp, top_down, masks, palette, palette_entry, stride, data })
+    }
+
+    fn channel(px: u32, mask: u32) -> u8 {
+        if mask == 0 {
+            return 0
+        }
+        let shift = mask.trailing_zeros();
+        let max = mask >> shift;
+        scale((px & mask) >> shift, max)
+    }
+
+    fn decode<P: Convert>(&self, target: &mut Target<P>) -> Result<(), DecodeError> {
+        for row in 0..self.h as usize {
+            let y = if self.top_down { row } else { self.h as usize - 1 - row };
+            let mut data = &self.data[row * self.stride..];
+            for x in 0..self.w as usize {
+                let c = match self.bpp {
+                    1 | 4 | 8 => {
+                        let bits = self.bpp as usize;
+                        let byte = data[x * bits / 8];
+                        let i = (byte >> (8 - bits - x * bits % 8)) as usize & ((1 << bits) - 1);
+                        let entry = self.palette.get(i * self.palette_entry..i * self.palette_entry + 3)
+                            .ok_or(DecodeError::Malformed)?;
+                        Rgba8888 { r: entry[2], g: entry[1], b: entry[0], a: 0xff }
+                    },
+                    24 => {
+                        let (p, rest) = data.split_at(3);
+                        data = rest;
+                        Rgba8888 { r: p[2], g: p[1], b: p[0], a: 0xff }
+                    },
+                    _ => {
+                        let px = if self.bpp == 16 {
+                            read_le_u16(&mut data).ok_or(DecodeError::Malformed)? as u32
+                        } else {
+                            read_le_u32(&mut data).ok_or(DecodeError::Malformed)?
+                        };
+                        let [r, g, b, a] = self.masks.map(|m| Self::channel(px, m));
+                        Rgba8888 { r, g, b, a: if self.masks[3] == 0 { 0xff } else { a } }
+                    },
+                };
+                target.put(x, y, c);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Binary PGM (`P5`) or PPM (`P6`) with 8 or 16 bit samples
+struct Pnm<'a> {
+    w: u16,
+    h: u16,
+    channels: usize,
+    max: u32,
+    data: &'a [u8]
+}
+
+impl<'a> Pnm<'a> {
+    /// Whitespace separated decimal, `#` starts comment up to the end of line
+    fn field(src: &[u8], i: &mut usize) -> Result<u32, DecodeError> {
+        let start = *i;
+        loop {
+            match src.get(*i) {
+                Some(b'#') => while src.get(*i).is_some_and(|c| *c != b'\n' && *c != b'\r') {
+                    *i += 1;
+                },
+                Some(c) if c.is_ascii_whitespace() => *i += 1,
+                _ => break,
+            }
+        }
+        if *i == start {
+            return Err(DecodeError::Malformed)
+        }
+        let mut v: u32 = 0;
+        let digits = *i;
+        while let Some(c) = src.get(*i).filter(|c| c.is_ascii_digit()) {
+            v = v.checked_mul(10).and_then(|v| v.checked_add((c - b'0') as u32)).ok_or(DecodeError::TooLarge)?;
+            *i += 1;
+        }
+        if *i == digits { Err(DecodeError::Malformed) } else { Ok(v) }
+    }
+
+    fn parse(src: &'a [u8]) -> Result<Self, DecodeError> {
+        let channels = match src.get(1) {
+            Some(b'5') => 1,
+            Some(b'6') => 3,
+            Some(b'1'..=b'4' | b'7') => return Err(DecodeError::Unsupported),
+            _ => return Err(DecodeError::UnknownFormat),
+        };
+        let mut i = 2;
+        let w = Self::field(src, &mut i)?;
+        let h = Self::field(src, &mut i)?;
+        let max = Self::field(src, &mut i)?;
+        // single whitespace before samples
+        if !src.get(i).is_some_and(|c| c.is_ascii_whitespace()) {
+            return Err(DecodeError::Malformed)
+        }
+        let (w, h) = check_size(w, h)?;
+        if max == 0 || max > u16::MAX as u32 {
+            return Err(DecodeError::Malformed)
+        }
+        let len = (w as usize * h as usize).checked_mul(channels * if max > 0xff { 2 } else { 1 });
+        let data = len.and_then(|len| src.get(i + 1..)?.get(..len)).ok_or(DecodeError::Malformed)?;
+        Ok(Self { w, h, channels, max, data })
+    }
+
+    fn decode<P: Convert>(&self, target: &mut Target<P>) -> Result<(), DecodeError> {
+        let wide = self.max > 0xff;
+        let sample = |i: usize| {
+            let v = if wide {
+                u16::from_be_bytes([self.data[i * 2], self.data[i * 2 + 1]]) as u32
+            } else {
+                self.data[i] as u32
+            };
+            scale(v.min(self.max), self.max)
+        };
+        for y in 0..self.h as usize {
+            for x in 0..self.w as usize {
+                let i = (y * self.w as usize + x) * self.channels;
+                let c = if self.channels == 1 {
+                    let v = sample(i);
+                    Rgba8888 { r: v, g: v, b: v, a: 0xff }
+                } else {
+                    Rgba8888 { r: sample(i), g: sample(i + 1), b: sample(i + 2), a: 0xff }
+                };
+                target.put(x, y, c);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) const QOI_OP_INDEX: u8 = 0x00;
+pub(crate) const QOI_OP_DIFF: u8 = 0x40;
+pub(crate) const QOI_OP_LUMA: u8 = 0x80;
+pub(crate) const QOI_OP_RUN: u8 = 0xc0;
+pub(crate) const QOI_OP_RGB: u8 = 0xfe;
+pub(crate) const QOI_OP_RGBA: u8 = 0xff;
+const QOI_HEADER_LEN: usize = 14;
+
+#[inline]
+pub(crate) fn qoi_hash(c: Rgba8888) -> usize {
+    (c.r as usize * 3 + c.g as usize * 5 + c.b as usize * 7 + c.a as usize * 11) % 64
+}
+
+/// Quite OK Image format
+struct Qoi<'a> {
+    w: u16,
+    h: u16,
+    data: &'a [u8]
+}
+
+impl<'a> Qoi<'a> {
+    fn parse(src: &'a [u8]) -> Result<Self, DecodeError> {
+        let mut header = src.get(4..QOI_HEADER_LEN).ok_or(DecodeError::Malformed)?;
+        let w = read_be_u32(&mut header).ok_or(DecodeError::Malformed)?;
+        let h = read_be_u32(&mut header).ok_or(DecodeError::Malformed)?;
+        // channels and colorspace are informative only
+        if !matches!(header, [3 | 4, 0 | 1]) {
+            return Err(DecodeError::Malformed)
+        }
+        let (w, h) = check_size(w, h)?;
+        Ok(Self { w, h, data: &src[QOI_HEADER_LEN..] })
+    }
+
+    fn decode<P: Convert>(&self, target: &mut Target<P>) -> Result<(), DecodeError> {
+        let mut index = [Rgba8888::default(); 64];
+        let mut px = Rgba8888 { r: 0, g: 0, b: 0, a: 0xff };
+        let mut data = self.data.iter().copied();
+        let mut next = || data.next().ok_or(DecodeError::Malformed);
+        let w = self.w as usize;
+        let total = w * self.h as usize;
+        let mut i = 0;
+        while i < total {
+            let op = next()?;
+            let mut run = 1;
+            match op {
+                QOI_OP_RGB => {
+                    px = Rgba8888 { r: next()?, g: next()?, b: next()?, a: px.a };
+                },
+                QOI_OP_RGBA => {
+                    px = Rgba8888 { r: next()?, g: next()?, b: next()?, a: next()? };
+                },
+                _ => match op & 0xc0 {
+                    QOI_OP_INDEX => px = index[op as usize],
+                    QOI_OP_DIFF => {
+                        let d = |shift: u8| ((op >> shift) & 0x03).wrapping_sub(2);
+                        px.r = px.r.wrapping_add(d(4));
+                        px.g = px.g.wrapping_add(d(2));
+                        px.b = px.b.wrapping_add(d(0));
+                    },
+                    QOI_OP_LUMA => {
+                        let dg = (op & 0x3f).wrapping_sub(32);
+                        let b = next()?;
+                        px.r = px.r.wrapping_add(dg.wrapping_add(b >> 4).wrapping_sub(8));
+                        px.g = px.g.wrapping_add(dg);
+                        px.b = px.b.wrapping_add(dg.wrapping_add(b & 0x0f).wrapping_sub(8));
+                    },
+                    tag => {
+                        debug_assert_eq!(tag, QOI_OP_RUN);
+                        run = (op & 0x3f) as usize + 1;
+                    },
+                },
+            }
+            if i + run > total {
+                return Err(DecodeError::Malformed)
+            }
+            index[qoi_hash(px)] = px;
+            for j in i..i + run {
+                target.put(j % w, j / w, px);
+            }
+            i += run;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{vec, vec::Vec};
+
+    use crate::pixel::{Argb8888, Gray8, Mono, Vga8};
+
+    use super::*;
+
+    const RED: Rgba8888 = Rgba8888 { r: 0xff, g: 0, b: 0, a: 0xff };
+    const GREEN: Rgba8888 = Rgba8888 { r: 0, g: 0xff, b: 0, a: 0xff };
+    const BLUE: Rgba8888 = Rgba8888 { r: 0, g: 0, b: 0xff, a: 0xff };
+    const WHITE: Rgba8888 = Rgba8888 { r: 0xff, g: 0xff, b: 0xff, a: 0xff };
+
+    /// Bitmap with 40 byte header, `extra` is palette or masks
+    fn bmp(w: i32, h: i32, bpp: u16, compression: u32, colors: u32, extra: &[u8], data: &[u8]) -> Vec<u8> {
+        let offset = 14 + 40 + extra.len() as u32;
+        let mut out = b"BM".to_vec();
+        out.extend((offset + data.len() as u32).to_le_bytes());
+        out.extend([0; 4]);
+        out.extend(offset.to_le_bytes());
+        out.extend(40u32.to_le_bytes());
+        out.extend(w.to_le_bytes());
+        out.extend(h.to_le_bytes());
+        out.extend(1u16.to_le_bytes());
+        out.extend(bpp.to_le_bytes());
+        out.extend(compression.to_le_bytes());
+        out.extend((data.len() as u32).to_le_bytes());
+        out.extend([0; 8]);
+        out.extend(colors.to_le_bytes());
+        out.extend([0; 4]);
+        out.extend(extra);
+        out.extend(data);
+        out
+    }
+
+    /// Image, its pixels and count of trailing bytes decoder does not need (row padding, end marker)
+    fn samples() -> Vec<(Vec<u8>, Vec<Rgba8888>, usize)> {
+        let bmp24 = bmp(2, 2, 24, BI_RGB, 0, &[], &[
+            0xff, 0, 0, 0xff, 0xff, 0xff, 0, 0,
+            0, 0, 0xff, 0, 0xff, 0, 0, 0,
+        ]);
+        // top down with 2 color palette
+        let bmp1 = bmp(3, -2, 1, BI_RGB, 2, &[0, 0, 0, 0, 0xff, 0xff, 0xff, 0], &[0xa0, 0, 0, 0, 0x40, 0, 0, 0]);
+        let black = Rgba8888 { r: 0, g: 0, b: 0, a: 0xff };
+        let masks565 = [0xf800_u32.to_le_bytes(), 0x07e0_u32.to_le_bytes(), 0x001f_u32.to_le_bytes()].concat();
+        let bmp16 = bmp(2, 1, 16, BI_BITFIELDS, 0, &masks565, &[0x00, 0xf8, 0xff, 0xff]);
+        let pgm = b"P5\n# comment\n2 2\n15\n\x00\x0f\x05\x0a".to_vec();
+        let gray = |v: u8| Rgba8888 { r: v, g: v, b: v, a: 0xff };
+        let ppm = b"P6 1 1 65535 \xff\xff\x80\x00\x00\x00".to_vec();
+        let mut qoi = b"qoif\0\0\0\x03\0\0\0\x02\x04\x00".to_vec();
+        qoi.extend([
+            QOI_OP_RGB, 0xff, 0, 0,
+            QOI_OP_RUN,
+            QOI_OP_DIFF | 0x1a,
+            QOI_OP_INDEX | 50,
+            QOI_OP_LUMA | 40, 0x88,
+            QOI_OP_RGBA, 1, 2, 3, 0x80,
+            0, 0, 0, 0, 0, 0, 0, 1
+        ]);
+        vec![
+            (bmp24, vec![RED, GREEN, BLUE, WHITE], 2),
+            (bmp1, vec![WHITE, black, WHITE, black, WHITE, black], 3),
+            (bmp16, vec![RED, WHITE], 0),
+            (pgm, vec![gray(0), gray(255), gray(85), gray(170)], 0),
+            (ppm, vec![Rgba8888 { r: 255, g: 128, b: 0, a: 0xff }], 0),
+            (qoi, vec![
+                RED, RED, Rgba8888 { r: 254, ..RED },
+                RED, Rgba8888 { r: 7, g: 8, b: 8, a: 0xff }, Rgba8888 { r: 1, g: 2, b: 3, a: 0x80 }
+            ], 8),
+        ]
+    }
+
+    fn decode_rgba(src: &[u8]) -> Result<Vec<Rgba8888>, DecodeError> {
+        let info = info(src)?;
+        let mut pixels = vec![Rgba8888::default(); info.w as usize * info.h as usize];
+        decode(src, &mut pixels)?;
+        Ok(pixels)
+    }
+
+    #[test]
+    fn decode_test() {
+        for (src, expected, _) in samples() {
+            assert_eq!(decode_rgba(&src), Ok(expected));
+        }
+        let (bmp1, _, _) = &samples()[1];
+        assert_eq!(info(bmp1), Ok(ImageInfo { format: ImageFormat::Bmp, w: 3, h: 2 }));
+        let mut mono = [Mono(0); 2];
+        decode(bmp1, &mut mono).unwrap();
+        assert_eq!(mono, [Mono(0b1010_0000), Mono(0b0100_0000)]);
+
+        let (bmp24, _, _) = &samples()[0];
+        let mut argb = [Argb8888(0); 4];
+        decode(bmp24, &mut argb).unwrap();
+        assert_eq!(argb[0], Argb8888(0xffff_0000));
+        let mut vga = [Vga8(0); 4];
+        decode(bmp24, &mut vga).unwrap();
+        assert_eq!(vga[3], Vga8(15));
+    }
+
+    #[test]
+    fn error_test() {
+        let (pgm, _, _) = &samples()[3];
+        assert_eq!(decode(pgm, &mut [Gray8(0); 3]), Err(DecodeError::SizeMismatch));
+        assert_eq!(info(b"GIF89a"), Err(DecodeError::UnknownFormat));
+        assert_eq!(info(b""), Err(DecodeError::UnknownFormat));
+        assert_eq!(info(b"P3 1 1 255 0 0 0"), Err(DecodeError::Unsupported));
+        assert_eq!(info(b"P5 70000 1 255 "), Err(DecodeError::TooLarge));
+        assert_eq!(info(b"P5 1 0 255 "), Err(DecodeError::Malformed));
+        assert_eq!(info(b"P5 99999999999 1 255 "), Err(DecodeError::TooLarge));
+        assert_eq!(info(&bmp(1, 1, 8, 1, 0, &[], &[0])), Err(DecodeError::Unsupported));
+        assert_eq!(info(&bmp(1, 1, 7, BI_RGB, 0, &[0; 512], &[0; 4])), Err(DecodeError::Malformed));
+        // palette of 8 bit bitmap is missing
+        assert_eq!(info(&bmp(1, 1, 8, BI_RGB, 0, &[], &[0; 4])), Err(DecodeError::Malformed));
+        // index outside of palette
+        assert_eq!(decode_rgba(&bmp(1, 1, 8, BI_RGB, 1, &[0; 4], &[1, 0, 0, 0])), Err(DecodeError::Malformed));
+        // run past last pixel
+        assert_eq!(decode_rgba(b"qoif\0\0\0\x01\0\0\0\x01\x04\x00\xc1"), Err(DecodeError::Malformed));
+    }
+
+    /// xorshift, tests must be reproducible
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as usize
+        }
+    }
+
+    #[test]
+    fn fuzz_test() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for (src, _, tail) in samples() {
+            for len in 0..src.len() - tail {
+                assert!(decode_rgba(&src[..len]).is_err(), "{:?} truncated to {}", &src[..4], len);
+            }
+            for _ in 0..20_000 {
+                let mut mutated = src.clone();
+                for _ in 0..1 + rng.next() % 4 {
+                    let i = rng.next() % mutated.len();
+                    mutated[i] = match rng.next() % 4 {
+                        0 => 0,
+                        1 => 0xff,
+                        2 => mutated[i] ^ (1 << (rng.next() % 8)),
+                        _ => rng.next() as u8,
+                    };
+                }
+                mutated.truncate(rng.next() % (src.len() + 1) + src.len() / 2);
+                // only must not panic, keep allocations small
+                if let Ok(info) = info(&mutated) {
+                    if (info.w as usize) * (info.h as usize) <= 1 << 16 {
+                        let _ = decode_rgba(&mutated);
+                    }
+                }
+            }
+        }
+    }
+}
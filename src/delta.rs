@@ -0,0 +1,179 @@
+use crate::winclient::Format;
+
+/// Frame layout delta is valid for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Layout {
+    pub format: Format,
+    pub pixel_size: u8,
+    pub w: u16,
+    pub h: u16
+}
+
+/// Copy of the last frame sent with `Client::present_delta`, kept in caller provided buffer.
+/// Use one history per client, frames which do not fit the buffer are always sent as keyframes
+pub struct DeltaHistory<'a> {
+    frame: &'a mut [u8],
+    len: usize,
+    layout: Option<Layout>
+}
+
+impl<'a> DeltaHistory<'a> {
+    pub fn new(frame: &'a mut [u8]) -> Self {
+        Self { frame, len: 0, layout: None }
+    }
+
+    /// Forget stored frame so next one is sent as keyframe
+    #[inline]
+    pub fn clear(&mut self) {
+        self.layout = None;
+    }
+
+    /// Previous frame if it has the same layout
+    pub(crate) fn base(&self, layout: Layout) -> Option<&[u8]> {
+        if self.layout == Some(layout) { Some(&self.frame[..self.len]) } else { None }
+    }
+
+    pub(crate) fn store(&mut self, layout: Layout, frame: &[u8]) {
+        match self.frame.get_mut(..frame.len()) {
+            Some(dst) => {
+                dst.copy_from_slice(frame);
+                self.len = frame.len();
+                self.layout = Some(layout);
+            },
+            None => self.layout = None,
+        }
+    }
+}
+
+/// Unchanged bytes shorter than this are sent inside copy run, separate run costs at least as much
+const MIN_GAP: usize = 4;
+
+fn push_varint(dst: &mut [u8], len: &mut usize, mut v: usize) -> Option<()> {
+    loop {
+        let b = (v & 0x7f) as u8;
+        v >>= 7;
+        *dst.get_mut(*len)? = if v > 0 { b | 0x80 } else { b };
+        *len += 1;
+        if v == 0 {
+            return Some(())
+        }
+    }
+}
+
+fn read_varint(src: &[u8], i: &mut usize) -> Option<usize> {
+    let mut v: usize = 0;
+    let mut shift = 0;
+    loop {
+        let b = *src.get(*i)?;
+        *i += 1;
+        v |= ((b & 0x7f) as usize).checked_shl(shift)?;
+        if b & 0x80 == 0 {
+            return Some(v)
+        }
+        shift += 7;
+    }
+}
+
+/// Encode `cur` against `prev` (same length) as runs of varint skip count,
+/// varint copy count and copied bytes. Unchanged tail is omitted.
+/// Returns `None` if output does not fit `dst`
+pub fn encode(prev: &[u8], cur: &[u8], dst: &mut [u8]) -> Option<usize> {
+    debug_assert_eq!(prev.len(), cur.len());
+    let n = prev.len().min(cur.len());
+    let mut len = 0;
+    let mut i = 0;
+    while let Some(start) = (i..n).find(|j| prev[*j] != cur[*j]) {
+        let mut end = start + 1;
+        let mut j = end;
+        while j < n && j - end < MIN_GAP {
+            if prev[j] != cur[j] {
+                end = j + 1;
+            }
+            j += 1;
+        }
+
+        push_varint(dst, &mut len, start - i)?;
+        push_varint(dst, &mut len, end - start)?;
+        dst.get_mut(len..len + end - start)?.copy_from_slice(&cur[start..end]);
+        len += end - start;
+        i = end;
+    }
+    Some(len)
+}
+
+/// Apply delta produced by `encode` to previous frame in place
+pub fn apply(src: &[u8], frame: &mut [u8]) -> Option<()> {
+    let mut i = 0;
+    let mut pos: usize = 0;
+    while i < src.len() {
+        pos = pos.checked_add(read_varint(src, &mut i)?)?;
+        let count = read_varint(src, &mut i)?;
+        frame.get_mut(pos..pos.checked_add(count)?)?.copy_from_slice(src.get(i..i.checked_add(count)?)?);
+        i += count;
+        pos += count;
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{vec, vec::Vec};
+
+    use super::*;
+
+    fn round_trip(prev: &[u8], cur: &[u8]) -> usize {
+        let mut encoded = vec![0; cur.len() * 2 + 16];
+        let len = encode(prev, cur, &mut encoded).unwrap();
+        let mut frame = prev.to_vec();
+        apply(&encoded[..len], &mut frame).unwrap();
+        assert_eq!(frame, cur);
+        len
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let prev: Vec<u8> = (0..100_000).map(|i| (i % 253) as u8).collect();
+
+        assert_eq!(round_trip(&prev, &prev), 0);
+
+        let mut cur = prev.clone();
+        cur[0] ^= 1;
+        cur[5] ^= 1;
+        cur[99_999] ^= 1;
+        for b in &mut cur[40_000..40_300] {
+            *b = 0;
+        }
+        // skip runs longer than one varint byte
+        assert!(round_trip(&prev, &cur) < 330);
+
+        let all_changed: Vec<u8> = prev.iter().map(|b| !b).collect();
+        round_trip(&prev, &all_changed);
+    }
+
+    #[test]
+    fn overflow_test() {
+        let prev = [0u8; 64];
+        let cur = [1u8; 64];
+        let mut small = [0; 16];
+        assert_eq!(encode(&prev, &cur, &mut small), None);
+        // copy past end of frame
+        let mut frame = [0u8; 4];
+        assert_eq!(apply(&[2, 3, 1, 2, 3], &mut frame), None);
+    }
+
+    #[test]
+    fn history_test() {
+        let layout = Layout { format: Format::GS, pixel_size: 1, w: 2, h: 2 };
+        let mut buf = [0; 4];
+        let mut history = DeltaHistory::new(&mut buf);
+        assert!(history.base(layout).is_none());
+        history.store(layout, &[1, 2, 3, 4]);
+        assert_eq!(history.base(layout), Some(&[1, 2, 3, 4][..]));
+        assert!(history.base(Layout { w: 4, h: 1, ..layout }).is_none());
+        // does not fit
+        history.store(layout, &[0; 8]);
+        assert!(history.base(layout).is_none());
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{
+    convert::Convert,
+    decode::{
+        qoi_hash,
+        QOI_OP_DIFF,
+        QOI_OP_INDEX,
+        QOI_OP_LUMA,
+        QOI_OP_RGB,
+        QOI_OP_RGBA,
+        QOI_OP_RUN
+    },
+    pixel::{self, Pixel, Rgba8888, Vga8},
+    sys::io_sys,
+    winclient::{Format, PALETTE_LEN},
+    write::WriteFd
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Binary PGM for `Format::GS` and `Format::MONO` frames, PPM for the rest. Alpha is dropped
+    Pnm,
+    /// QOI with alpha channel for formats which have it
+    Qoi,
+}
+
+#[derive(Debug)]
+pub enum DumpError {
+    /// `pixels` do not hold exactly `w` x `h` pixels
+    SizeMismatch,
+    WriteError(io_sys::ErrNo),
+}
+
+/// Write `w` x `h` frame exactly as it is passed to `Client::present`.
+/// `Format::VGA` frames are expanded through default palette, see `dump_vga` for custom one
+pub fn dump<P: Convert>(out: &mut WriteFd, format: DumpFormat, w: u16, h: u16, pixels: &[P]) -> Result<(), DumpError> {
+    if pixels.len() != pixel::surface_len::<P>(w, h) {
+        return Err(DumpError::SizeMismatch)
+    }
+    let row_len = pixel::row_len::<P>(w);
+    encode(out, format, w, h, P::FORMAT, |x, y| P::get(&pixels[y * row_len..], x))
+}
+
+/// `dump` of `Format::VGA` frame shown with `palette` of `0xAARRGGBB` colors,
+/// e.g. copy of `convert::VGA_PALETTE` with changes sent by `Client::set_palette`
+pub fn dump_vga(out: &mut WriteFd, format: DumpFormat, w: u16, h: u16, pixels: &[Vga8], palette: &[u32; PALETTE_LEN]) -> Result<(), DumpError> {
+    if pixels.len() != pixel::surface_len::<Vga8>(w, h) {
+        return Err(DumpError::SizeMismatch)
+    }
+    encode(out, format, w, h, Vga8::FORMAT, |x, y| {
+        let c = palette[pixels[y * w as usize + x].0 as usize];
+        Rgba8888 { r: (c >> 16) as u8, g: (c >> 8) as u8, b: c as u8, a: 0xff }
+    })
+}
+
+/// Buffers small writes of encoders
+struct Out<'a> {
+    fd: &'a mut WriteFd,
+    buf: [u8; 1024],
+    len: usize
+}
+
+impl Out<'_> {
+    #[inline]
+    fn push(&mut self, bytes: &[u8]) -> Result<(), io_sys::ErrNo> {
+        if self.len + bytes.len() > self.buf.len() {
+            self.flush()?;
+        }
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    fn push_decimal(&mut self, mut v: u16) -> Result<(), io_sys::ErrNo> {
+        let mut digits = [0; 5];
+        let mut i = digits.len();
+        loop {
+            i -= 1;
+            digits[i] = b'0' + (v % 10) as u8;
+            v /= 10;
+            if v == 0 {
+                break
+            }
+        }
+        self.push(&digits[i..])
+    }
+
+    fn flush(&mut self) -> Result<(), io_sys::ErrNo> {
+        let len = self.len;
+        self.len = 0;
+        self.fd.write_all(&self.buf[..len])
+    }
+}
+
+fn encode(fd: &mut WriteFd, format: DumpFormat, w: u16, h: u16, pixel_format: Format, get: impl Fn(usize, usize) -> Rgba8888) -> Result<(), DumpError> {
+    let mut out = Out { fd, buf: [0; 1024], len: 0 };
+    match format {
+        DumpFormat::Pnm => write_pnm(&mut out, w, h, matches!(pixel_format, Format::GS | Format::MONO), get),
+        DumpFormat::Qoi => write_qoi(&mut out, w, h, matches!(pixel_format, Format::ARGB | Format::RGBA | Format::PRGBA), get),
+    }
+        .and_then(|_| out.flush())
+        .map_err(DumpError::WriteError)
+}
+
+fn write_pnm(out: &mut Out, w: u16, h: u16, gray: bool, get: impl Fn(usize, usize) -> Rgba8888) -> Result<(), io_sys::ErrNo> {
+    out.push(if gray { b"P5\n" } else { b"P6\n" })?;
+    out.push_decimal(w)?;
+    out.push(b" ")?;
+    out.push_decimal(h)?;
+    out.push(b"\n255\n")?;
+    for y in 0..h as usize {
+        for x in 0..w as usize {
+            let c = get(x, y);
+            if gray {
+                out.push(&[c.r])?;
+            } else {
+                out.push(&[c.r, c.g, c.b])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Longest run of single `QOI_OP_RUN`
+const QOI_MAX_RUN: u8 = 62;
+
+fn write_qoi(out: &mut Out, w: u16, h: u16, alpha: bool, get: impl Fn(usize, usize) -> Rgba8888) -> Result<(), io_sys::ErrNo> {
+    out.push(b"qoif")?;
+    out.push(&(w as u32).to_be_bytes())?;
+    out.push(&(h as u32).to_be_bytes())?;
+    // sRGB with linear alpha
+    out.push(&[if alpha { 4 } else { 3 }, 0])?;
+
+    let mut index = [Rgba8888::default(); 64];
+    let mut prev = Rgba8888 { r: 0, g: 0, b: 0, a: 0xff };
+    let mut run = 0;
+    for y in 0..h as usize {
+        for x in 0..w as usize {
+            let px = get(x, y);
+            if px == prev {
+                run += 1;
+                if run == QOI_MAX_RUN {
+                    out.push(&[QOI_OP_RUN | (run - 1)])?;
+                    run = 0;
+                }
+                continue
+            }
+            if run > 0 {
+                out.push(&[QOI_OP_RUN | (run - 1)])?;
+                run = 0;
+            }
+
+            let hash = qoi_hash(px);
+            if index[hash] == px {
+                out.push(&[QOI_OP_INDEX | hash as u8])?;
+            } else if px.a != prev.a {
+                out.push(&[QOI_OP_RGBA, px.r, px.g, px.b, px.a])?;
+            } else {
+                let dr = px.r.wrapping_sub(prev.r) as i8;
+                let dg = px.g.wrapping_sub(prev.g) as i8;
+                let db = px.b.wrapping_sub(prev.b) as i8;
+                let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+                if (-2..2).contains(&dr) && (-2..2).contains(&dg) && (-2..2).contains(&db) {
+                    out.push(&[QOI_OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8])?;
+                } else if (-32..32).contains(&dg) && (-8..8).contains(&dr_dg) && (-8..8).contains(&db_dg) {
+                    out.push(&[QOI_OP_LUMA | (dg + 32) as u8, ((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8])?;
+                } else {
+                    out.push(&[QOI_OP_RGB, px.r, px.g, px.b])?;
+                }
+            }
+            index[hash] = px;
+            prev = px;
+        }
+    }
+    if run > 0 {
+        out.push(&[QOI_OP_RUN | (run - 1)])?;
+    }
+    out.push(&[0, 0, 0, 0, 0, 0, 0, 1])
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{ffi::CString, fs, os::unix::ffi::OsStrExt, vec, vec::Vec};
+
+    use crate::{
+        convert::VGA_PALETTE,
+        decode,
+        pixel::{Argb8888, Gray8, Mono, Rgb565},
+        write::FileFd
+    };
+
+    use super::*;
+
+    /// Bytes written to real file by `f`
+    fn written(name: &str, f: impl FnOnce(&mut WriteFd) -> Result<(), DumpError>) -> Vec<u8> {
+        let path = std::env::temp_dir().join(std::format!("mini_winclient_{}_{}", std::process::id(), name));
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        // longer stale file is truncated
+        fs::write(&path, [0xff; 4096]).unwrap();
+        {
+            let mut file = FileFd::create(&c_path).unwrap();
+            f(&mut file).unwrap();
+        }
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn decode_rgba(src: &[u8]) -> Vec<Rgba8888> {
+        let info = decode::info(src).unwrap();
+        let mut pixels = vec![Rgba8888::default(); info.w as usize * info.h as usize];
+        decode::decode(src, &mut pixels).unwrap();
+        pixels
+    }
+
+    #[test]
+    fn pnm_test() {
+        let gray = [Gray8(0), Gray8(1), Gray8(254), Gray8(255)];
+        let pgm = written("pgm", |out| dump(out, DumpFormat::Pnm, 2, 2, &gray));
+        assert_eq!(pgm, b"P5\n2 2\n255\n\x00\x01\xfe\xff");
+
+        let mono = [Mono(0b1010_0000), Mono(0b0100_0000)];
+        let pgm = written("mono", |out| dump(out, DumpFormat::Pnm, 3, 2, &mono));
+        assert_eq!(pgm, b"P5\n3 2\n255\n\xff\x00\xff\x00\xff\x00");
+
+        let rgb = [Rgb565::new(0xff, 0, 0), Rgb565::new(0, 0, 0xff)];
+        let ppm = written("ppm", |out| dump(out, DumpFormat::Pnm, 1, 2, &rgb));
+        assert_eq!(ppm, b"P6\n1 2\n255\n\xff\x00\x00\x00\x00\xff");
+    }
+
+    #[test]
+    fn qoi_test() {
+        let red = Rgba8888 { r: 0xff, g: 0, b: 0, a: 0xff };
+        let pixels = [
+            red, red, Rgba8888 { r: 254, ..red },
+            red, Rgba8888 { r: 7, g: 8, b: 8, a: 0xff }, Rgba8888 { r: 1, g: 2, b: 3, a: 0x80 }
+        ];
+        let qoi = written("qoi", |out| dump(out, DumpFormat::Qoi, 3, 2, &pixels));
+        assert_eq!(qoi, [
+            b'q', b'o', b'i', b'f', 0, 0, 0, 3, 0, 0, 0, 2, 4, 0,
+            // red differs from initial black by -1
+            QOI_OP_DIFF | 0x1a,
+            QOI_OP_RUN,
+            QOI_OP_DIFF | 0x1a,
+            QOI_OP_INDEX | 50,
+            QOI_OP_LUMA | 40, 0x88,
+            QOI_OP_RGBA, 1, 2, 3, 0x80,
+            0, 0, 0, 0, 0, 0, 0, 1
+        ]);
+        assert_eq!(decode_rgba(&qoi), pixels);
+
+        // runs longer than one op, frame larger than output buffer
+        let black = vec![Gray8(0); 200];
+        let qoi = written("run", |out| dump(out, DumpFormat::Qoi, 20, 10, &black));
+        assert_eq!(&qoi[12..], &[3, 0, 0xfd, 0xfd, 0xfd, 0xcd, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let argb: Vec<Argb8888> = (0..64 * 48u32).map(|i| Argb8888(i.wrapping_mul(0x0102_0305))).collect();
+        let qoi = written("argb", |out| dump(out, DumpFormat::Qoi, 64, 48, &argb));
+        let mut decoded = vec![Argb8888(0); argb.len()];
+        decode::decode(&qoi, &mut decoded).unwrap();
+        assert_eq!(decoded, argb);
+    }
+
+    #[test]
+    fn vga_test() {
+        let pixels = [Vga8(1), Vga8(2), Vga8(1)];
+        let mut palette = VGA_PALETTE;
+        palette[1] = 0xff10_2030;
+        let ppm = written("vga", |out| dump_vga(out, DumpFormat::Pnm, 3, 1, &pixels, &palette));
+        assert_eq!(&ppm[11..], &[0x10, 0x20, 0x30, 0x14, 0xaa, 0x00, 0x10, 0x20, 0x30]);
+
+        let default = written("vga_default", |out| dump(out, DumpFormat::Qoi, 3, 1, &pixels));
+        let decoded = decode_rgba(&default);
+        assert_eq!(decoded[0], Rgba8888 { r: 0x00, g: 0x02, b: 0xaa, a: 0xff });
+        assert_eq!(decoded[0], decoded[2]);
+    }
+
+    #[test]
+    fn error_test() {
+        let mut out = WriteFd::new(crate::STDOUT);
+        assert!(matches!(dump(&mut out, DumpFormat::Pnm, 2, 2, &[Gray8(0); 3]), Err(DumpError::SizeMismatch)));
+        assert!(matches!(dump_vga(&mut out, DumpFormat::Qoi, 2, 2, &[Vga8(0); 5], &VGA_PALETTE), Err(DumpError::SizeMismatch)));
+        assert!(matches!(FileFd::open(c"/nonexistent/frame.ppm"), Err(io_sys::errno::ENOENT)));
+        assert!(matches!(FileFd::create(c"/nonexistent/frame.ppm"), Err(io_sys::errno::ENOENT)));
+        assert!(FileFd::open(c"/dev/null").is_ok());
+    }
+}
@@ -1,36 +1,160 @@
-use crate::bufsocket::BufSocket;
+use core::str;
+
+use crate::{bufsocket::BufSocket, keys::KeyCode};
 
 mod event_types {
     pub const CLOSE: u8 = 0;
     pub const RESIZE: u8 = 1;
+    pub const MOUSE_DOWN: u8 = 2;
+    pub const MOUSE_UP: u8 = 3;
+    pub const MOUSE_MOVE: u8 = 4;
+    pub const KEY_DOWN: u8 = 5;
+    pub const KEY_UP: u8 = 6;
+}
+
+pub type MouseButtons = u8;
+
+pub mod mouse_buttons {
+    use super::MouseButtons;
+
+    pub const LEFT: MouseButtons = 1 << 0;
+    pub const RIGHT: MouseButtons = 1 << 1;
+    pub const MIDDLE: MouseButtons = 1 << 2;
+}
+
+pub type Modifiers = u8;
+
+pub mod modifiers {
+    use super::Modifiers;
+
+    pub const SHIFT: Modifiers = 1 << 0;
+    pub const CONTROL: Modifiers = 1 << 1;
+    pub const ALT: Modifiers = 1 << 2;
+    pub const META: Modifiers = 1 << 3;
+    pub const KEYPAD: Modifiers = 1 << 4;
+}
+
+/// Max bytes of utf-8 text carried by a key event. Longer text is truncated by server
+pub const KEY_TEXT_CAPACITY: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct KeyEvent {
+    /// Platform dependent scan code of the physical key
+    pub scan_code: u32,
+    pub key: KeyCode,
+    pub modifiers: Modifiers,
+    text_len: u8,
+    text: [u8; KEY_TEXT_CAPACITY]
+}
+
+impl KeyEvent {
+    /// Text produced by the key press (if any)
+    #[inline]
+    pub fn text(&self) -> Option<&str> {
+        if self.text_len > 0 {
+            str::from_utf8(&self.text[..self.text_len as usize]).ok()
+        } else {
+            None
+        }
+    }
+
+    fn pull<const CAPACITY: usize, const CHUNK_LEN: usize>(s: &mut BufSocket<CAPACITY, CHUNK_LEN>, len: &mut usize) -> Result<KeyEvent, Error> {
+        let mut e = KeyEvent {
+            scan_code: 0,
+            key: 0,
+            modifiers: 0,
+            text_len: 0,
+            text: [0; KEY_TEXT_CAPACITY]
+        };
+        let mut text_len: u8 = 0;
+        if !(s.read_transmuted_limited(&mut e.scan_code, len)
+            && s.read_transmuted_limited(&mut e.key, len)
+            && s.read_transmuted_limited(&mut e.modifiers, len)
+            && s.read_transmuted_limited(&mut text_len, len)) {
+            return Err(Error::TooShort)
+        }
+
+        let text_len = text_len as usize;
+        if text_len > *len {
+            return Err(Error::TooShort)
+        }
+        let stored_len = text_len.min(KEY_TEXT_CAPACITY);
+        if s.read_bytes(&mut e.text[..stored_len]) + s.discard(text_len - stored_len) != text_len {
+            return Err(Error::TooShort)
+        }
+        *len -= text_len;
+        e.text_len = stored_len as u8;
+        Ok(e)
+    }
 }
 
 #[derive(Debug)]
 pub enum Event {
     Close,
-    Resize{w: u16, h: u16}
+    Resize{w: u16, h: u16},
+    /// `button` is the single button that was pressed
+    MouseDown{x: u16, y: u16, button: MouseButtons},
+    /// `button` is the single button that was released
+    MouseUp{x: u16, y: u16, button: MouseButtons},
+    /// `buttons` is the set of buttons held during the move
+    MouseMove{x: u16, y: u16, buttons: MouseButtons},
+    KeyDown(KeyEvent),
+    KeyUp(KeyEvent)
 }
 
 #[derive(Debug)]
 pub enum Error {
-    UnknownType(u8)
+    UnknownType(u8),
+    /// Package ends before last field of event
+    TooShort
 }
 
 impl Event {
-    pub fn pull<const CAPACITY: usize, const CHUNK_LEN: usize>(s: &mut BufSocket<CAPACITY, CHUNK_LEN>) -> Result<Event, Error> {
+    /// Pull event from package with `len` bytes left, `len` is decreased by count of consumed bytes
+    pub fn pull<const CAPACITY: usize, const CHUNK_LEN: usize>(s: &mut BufSocket<CAPACITY, CHUNK_LEN>, len: &mut usize) -> Result<Event, Error> {
         let mut _type: u8 = 0;
-        assert!(s.read_transmuted(&mut _type));
+        if !s.read_transmuted_limited(&mut _type, len) {
+            return Err(Error::TooShort)
+        }
 
         match _type {
             event_types::CLOSE => Ok(Event::Close),
             event_types::RESIZE => {
                 let mut w: u16 = 0;
                 let mut h: u16 = 0;
-                assert!(s.read_transmuted(&mut w));
-                assert!(s.read_transmuted(&mut h));
+                if !(s.read_transmuted_limited(&mut w, len) && s.read_transmuted_limited(&mut h, len)) {
+                    return Err(Error::TooShort)
+                }
                 Ok(Event::Resize { w, h })
             }
+            event_types::MOUSE_DOWN => {
+                let (x, y, button) = Self::pull_mouse(s, len)?;
+                Ok(Event::MouseDown { x, y, button })
+            }
+            event_types::MOUSE_UP => {
+                let (x, y, button) = Self::pull_mouse(s, len)?;
+                Ok(Event::MouseUp { x, y, button })
+            }
+            event_types::MOUSE_MOVE => {
+                let (x, y, buttons) = Self::pull_mouse(s, len)?;
+                Ok(Event::MouseMove { x, y, buttons })
+            }
+            event_types::KEY_DOWN => KeyEvent::pull(s, len).map(Event::KeyDown),
+            event_types::KEY_UP => KeyEvent::pull(s, len).map(Event::KeyUp),
             t => Err(Error::UnknownType(t))
         }
     }
+
+    fn pull_mouse<const CAPACITY: usize, const CHUNK_LEN: usize>(s: &mut BufSocket<CAPACITY, CHUNK_LEN>, len: &mut usize) -> Result<(u16, u16, MouseButtons), Error> {
+        let mut x: u16 = 0;
+        let mut y: u16 = 0;
+        let mut buttons: MouseButtons = 0;
+        if s.read_transmuted_limited(&mut x, len)
+            && s.read_transmuted_limited(&mut y, len)
+            && s.read_transmuted_limited(&mut buttons, len) {
+            Ok((x, y, buttons))
+        } else {
+            Err(Error::TooShort)
+        }
+    }
 }
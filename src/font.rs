@@ -0,0 +1,277 @@
+use crate::canvas::{Canvas, Plot};
+
+/// Fixed width bitmap font, one byte per glyph row with leftmost pixel in the least significant bit
+#[derive(Debug, Clone, Copy)]
+pub struct Font {
+    w: u8,
+    h: u8,
+    first: char,
+    glyphs: &'static [u8]
+}
+
+impl Font {
+    /// `glyphs` holds `h` rows of consecutive characters starting at `first`, `w` is at most 8
+    pub const fn new(w: u8, h: u8, first: char, glyphs: &'static [u8]) -> Self {
+        assert!(w > 0 && w <= 8 && h > 0 && glyphs.len().is_multiple_of(h as usize));
+        Self { w, h, first, glyphs }
+    }
+
+    #[inline]
+    pub fn w(&self) -> u8 {
+        self.w
+    }
+
+    #[inline]
+    pub fn h(&self) -> u8 {
+        self.h
+    }
+
+    fn index(&self, c: char) -> Option<usize> {
+        let i = (c as u32).checked_sub(self.first as u32)? as usize;
+        if i < self.glyphs.len() / self.h as usize { Some(i) } else { None }
+    }
+
+    /// Rows of `c`, `'?'` for characters missing in font
+    pub fn glyph(&self, c: char) -> &'static [u8] {
+        let h = self.h as usize;
+        match self.index(c).or_else(|| self.index('?')) {
+            Some(i) => &self.glyphs[i * h..(i + 1) * h],
+            None => &[],
+        }
+    }
+
+    /// Width of the longest line and height of all lines of `text` in pixels
+    pub fn measure(&self, text: &str) -> (u32, u32) {
+        let mut lines = 0;
+        let mut w = 0;
+        for line in text.split('\n') {
+            lines += 1;
+            w = w.max(line.chars().count() as u32 * self.w as u32);
+        }
+        (w, lines * self.h as u32)
+    }
+}
+
+/// Printable ASCII of the public domain 8x8 IBM PC BIOS font
+const FONT_8X8_GLYPHS: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x18, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x18, 0x00], // !
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x36, 0x36, 0x7f, 0x36, 0x7f, 0x36, 0x36, 0x00], // #
+    [0x0c, 0x3e, 0x03, 0x1e, 0x30, 0x1f, 0x0c, 0x00], // $
+    [0x00, 0x63, 0x33, 0x18, 0x0c, 0x66, 0x63, 0x00], // %
+    [0x1c, 0x36, 0x1c, 0x6e, 0x3b, 0x33, 0x6e, 0x00], // &
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x18, 0x0c, 0x06, 0x06, 0x06, 0x0c, 0x18, 0x00], // (
+    [0x06, 0x0c, 0x18, 0x18, 0x18, 0x0c, 0x06, 0x00], // )
+    [0x00, 0x66, 0x3c, 0xff, 0x3c, 0x66, 0x00, 0x00], // *
+    [0x00, 0x0c, 0x0c, 0x3f, 0x0c, 0x0c, 0x00, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c, 0x06], // ,
+    [0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c, 0x00], // .
+    [0x60, 0x30, 0x18, 0x0c, 0x06, 0x03, 0x01, 0x00], // /
+    [0x3e, 0x63, 0x73, 0x7b, 0x6f, 0x67, 0x3e, 0x00], // 0
+    [0x0c, 0x0e, 0x0c, 0x0c, 0x0c, 0x0c, 0x3f, 0x00], // 1
+    [0x1e, 0x33, 0x30, 0x1c, 0x06, 0x33, 0x3f, 0x00], // 2
+    [0x1e, 0x33, 0x30, 0x1c, 0x30, 0x33, 0x1e, 0x00], // 3
+    [0x38, 0x3c, 0x36, 0x33, 0x7f, 0x30, 0x78, 0x00], // 4
+    [0x3f, 0x03, 0x1f, 0x30, 0x30, 0x33, 0x1e, 0x00], // 5
+    [0x1c, 0x06, 0x03, 0x1f, 0x33, 0x33, 0x1e, 0x00], // 6
+    [0x3f, 0x33, 0x30, 0x18, 0x0c, 0x0c, 0x0c, 0x00], // 7
+    [0x1e, 0x33, 0x33, 0x1e, 0x33, 0x33, 0x1e, 0x00], // 8
+    [0x1e, 0x33, 0x33, 0x3e, 0x30, 0x18, 0x0e, 0x00], // 9
+    [0x00, 0x0c, 0x0c, 0x00, 0x00, 0x0c, 0x0c, 0x00], // :
+    [0x00, 0x0c, 0x0c, 0x00, 0x00, 0x0c, 0x0c, 0x06], // ;
+    [0x18, 0x0c, 0x06, 0x03, 0x06, 0x0c, 0x18, 0x00], // <
+    [0x00, 0x00, 0x3f, 0x00, 0x00, 0x3f, 0x00, 0x00], // =
+    [0x06, 0x0c, 0x18, 0x30, 0x18, 0x0c, 0x06, 0x00], // >
+    [0x1e, 0x33, 0x30, 0x18, 0x0c, 0x00, 0x0c, 0x00], // ?
+    [0x3e, 0x63, 0x7b, 0x7b, 0x7b, 0x03, 0x1e, 0x00], // @
+    [0x0c, 0x1e, 0x33, 0x33, 0x3f, 0x33, 0x33, 0x00], // A
+    [0x3f, 0x66, 0x66, 0x3e, 0x66, 0x66, 0x3f, 0x00], // B
+    [0x3c, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3c, 0x00], // C
+    [0x1f, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1f, 0x00], // D
+    [0x7f, 0x46, 0x16, 0x1e, 0x16, 0x46, 0x7f, 0x00], // E
+    [0x7f, 0x46, 0x16, 0x1e, 0x16, 0x06, 0x0f, 0x00], // F
+    [0x3c, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7c, 0x00], // G
+    [0x33, 0x33, 0x33, 0x3f, 0x33, 0x33, 0x33, 0x00], // H
+    [0x1e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // I
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1e, 0x00], // J
+    [0x67, 0x66, 0x36, 0x1e, 0x36, 0x66, 0x67, 0x00], // K
+    [0x0f, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7f, 0x00], // L
+    [0x63, 0x77, 0x7f, 0x7f, 0x6b, 0x63, 0x63, 0x00], // M
+    [0x63, 0x67, 0x6f, 0x7b, 0x73, 0x63, 0x63, 0x00], // N
+    [0x1c, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1c, 0x00], // O
+    [0x3f, 0x66, 0x66, 0x3e, 0x06, 0x06, 0x0f, 0x00], // P
+    [0x1e, 0x33, 0x33, 0x33, 0x3b, 0x1e, 0x38, 0x00], // Q
+    [0x3f, 0x66, 0x66, 0x3e, 0x36, 0x66, 0x67, 0x00], // R
+    [0x1e, 0x33, 0x07, 0x0e, 0x38, 0x33, 0x1e, 0x00], // S
+    [0x3f, 0x2d, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // T
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3f, 0x00], // U
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1e, 0x0c, 0x00], // V
+    [0x63, 0x63, 0x63, 0x6b, 0x7f, 0x77, 0x63, 0x00], // W
+    [0x63, 0x63, 0x36, 0x1c, 0x1c, 0x36, 0x63, 0x00], // X
+    [0x33, 0x33, 0x33, 0x1e, 0x0c, 0x0c, 0x1e, 0x00], // Y
+    [0x7f, 0x63, 0x31, 0x18, 0x4c, 0x66, 0x7f, 0x00], // Z
+    [0x1e, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1e, 0x00], // [
+    [0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x40, 0x00], // \
+    [0x1e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1e, 0x00], // ]
+    [0x08, 0x1c, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff], // _
+    [0x0c, 0x0c, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x1e, 0x30, 0x3e, 0x33, 0x6e, 0x00], // a
+    [0x07, 0x06, 0x06, 0x3e, 0x66, 0x66, 0x3b, 0x00], // b
+    [0x00, 0x00, 0x1e, 0x33, 0x03, 0x33, 0x1e, 0x00], // c
+    [0x38, 0x30, 0x30, 0x3e, 0x33, 0x33, 0x6e, 0x00], // d
+    [0x00, 0x00, 0x1e, 0x33, 0x3f, 0x03, 0x1e, 0x00], // e
+    [0x1c, 0x36, 0x06, 0x0f, 0x06, 0x06, 0x0f, 0x00], // f
+    [0x00, 0x00, 0x6e, 0x33, 0x33, 0x3e, 0x30, 0x1f], // g
+    [0x07, 0x06, 0x36, 0x6e, 0x66, 0x66, 0x67, 0x00], // h
+    [0x0c, 0x00, 0x0e, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // i
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1e], // j
+    [0x07, 0x06, 0x66, 0x36, 0x1e, 0x36, 0x67, 0x00], // k
+    [0x0e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // l
+    [0x00, 0x00, 0x33, 0x7f, 0x7f, 0x6b, 0x63, 0x00], // m
+    [0x00, 0x00, 0x1f, 0x33, 0x33, 0x33, 0x33, 0x00], // n
+    [0x00, 0x00, 0x1e, 0x33, 0x33, 0x33, 0x1e, 0x00], // o
+    [0x00, 0x00, 0x3b, 0x66, 0x66, 0x3e, 0x06, 0x0f], // p
+    [0x00, 0x00, 0x6e, 0x33, 0x33, 0x3e, 0x30, 0x78], // q
+    [0x00, 0x00, 0x3b, 0x6e, 0x66, 0x06, 0x0f, 0x00], // r
+    [0x00, 0x00, 0x3e, 0x03, 0x1e, 0x30, 0x1f, 0x00], // s
+    [0x08, 0x0c, 0x3e, 0x0c, 0x0c, 0x2c, 0x18, 0x00], // t
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6e, 0x00], // u
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1e, 0x0c, 0x00], // v
+    [0x00, 0x00, 0x63, 0x6b, 0x7f, 0x7f, 0x36, 0x00], // w
+    [0x00, 0x00, 0x63, 0x36, 0x1c, 0x36, 0x63, 0x00], // x
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3e, 0x30, 0x1f], // y
+    [0x00, 0x00, 0x3f, 0x19, 0x0c, 0x26, 0x3f, 0x00], // z
+    [0x38, 0x0c, 0x0c, 0x07, 0x0c, 0x0c, 0x38, 0x00], // {
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // |
+    [0x07, 0x0c, 0x0c, 0x38, 0x0c, 0x0c, 0x07, 0x00], // }
+    [0x6e, 0x3b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ~
+];
+
+pub const FONT_8X8: Font = Font::new(8, 8, ' ', FONT_8X8_GLYPHS.as_flattened());
+
+impl<P: Plot> Canvas<'_, P> {
+    /// Draw glyph cell of `c` with top left corner at `x`, `y`.
+    /// `bg` fills unset glyph pixels, `None` leaves them untouched
+    pub fn glyph(&mut self, x: i32, y: i32, c: char, font: &Font, fg: P::Color, bg: Option<P::Color>) {
+        if x >= self.w() as i32 || y >= self.h() as i32
+            || x.saturating_add(font.w as i32) <= 0 || y.saturating_add(font.h as i32) <= 0 {
+            return
+        }
+        let glyph = font.glyph(c);
+        for gy in 0..font.h as i32 {
+            let bits = glyph.get(gy as usize).copied().unwrap_or(0);
+            for gx in 0..font.w as i32 {
+                if bits & (1 << gx) != 0 {
+                    self.set_pixel(x + gx, y + gy, fg);
+                } else if let Some(bg) = bg {
+                    self.set_pixel(x + gx, y + gy, bg);
+                }
+            }
+        }
+    }
+
+    /// Draw `text` starting at `x`, `y`, `'\n'` starts next line at `x`.
+    /// Returns position where next character would be drawn
+    pub fn text(&mut self, x: i32, y: i32, text: &str, font: &Font, fg: P::Color, bg: Option<P::Color>) -> (i32, i32) {
+        let (mut cx, mut cy) = (x, y);
+        for c in text.chars() {
+            if c == '\n' {
+                cx = x;
+                cy = cy.saturating_add(font.h as i32);
+                continue
+            }
+            self.glyph(cx, cy, c, font, fg, bg);
+            cx = cx.saturating_add(font.w as i32);
+        }
+        (cx, cy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{string::String, vec, vec::Vec};
+
+    use crate::pixel::{Gray8, Mono};
+
+    use super::*;
+
+    fn render(canvas: &Canvas<Gray8>) -> Vec<String> {
+        (0..canvas.h() as i32)
+            .map(|y| (0..canvas.w() as i32).map(|x| match canvas.pixel(x, y).unwrap().0 {
+                0 => '.',
+                1 => '-',
+                _ => '#',
+            }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn glyph_test() {
+        assert_eq!(FONT_8X8.glyph('A'), &[0x0c, 0x1e, 0x33, 0x33, 0x3f, 0x33, 0x33, 0x00]);
+        assert_eq!(FONT_8X8.glyph('~'), &[0x6e, 0x3b, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(FONT_8X8.glyph('\u{7f}'), FONT_8X8.glyph('?'));
+        assert_eq!(FONT_8X8.glyph('ж'), FONT_8X8.glyph('?'));
+        assert_eq!(FONT_8X8.measure(""), (0, 8));
+        assert_eq!(FONT_8X8.measure("ab\nabc\n"), (24, 24));
+    }
+
+    #[test]
+    fn text_test() {
+        let mut pixels = vec![Gray8(0); 16 * 18];
+        let mut canvas = Canvas::new(16, 18, &mut pixels).unwrap();
+        let end = canvas.text(0, 0, "Hi\n.", &FONT_8X8, Gray8(255), Some(Gray8(1)));
+        assert_eq!(end, (8, 8));
+        assert_eq!(render(&canvas), [
+            "##--##----##----",
+            "##--##----------",
+            "##--##---###----",
+            "######----##----",
+            "##--##----##----",
+            "##--##----##----",
+            "##--##---####---",
+            "----------------",
+            "--------........",
+            "--------........",
+            "--------........",
+            "--------........",
+            "--------........",
+            "--##----........",
+            "--##----........",
+            "--------........",
+            "................",
+            "................",
+        ]);
+    }
+
+    #[test]
+    fn clip_test() {
+        let mut pixels = vec![Gray8(0); 6 * 4];
+        let mut canvas = Canvas::new(6, 4, &mut pixels).unwrap();
+        // only bottom right of 'H' and top left of 'i' are visible
+        canvas.text(-4, -3, "Hi", &FONT_8X8, Gray8(255), None);
+        assert_eq!(render(&canvas), [
+            "##....",
+            "##....",
+            "##....",
+            "##...#",
+        ]);
+        // completely outside
+        canvas.text(i32::MAX - 4, i32::MIN, "Hi\nHi", &FONT_8X8, Gray8(1), Some(Gray8(1)));
+        canvas.text(-16, 0, "Hi", &FONT_8X8, Gray8(1), Some(Gray8(1)));
+        assert_eq!(canvas.pixels().iter().filter(|p| p.0 == 1).count(), 0);
+    }
+
+    #[test]
+    fn mono_test() {
+        let mut pixels = vec![Mono(0xff); 2 * 8];
+        let mut canvas = Canvas::new(12, 8, &mut pixels).unwrap();
+        canvas.text(2, 0, "T", &FONT_8X8, false, None);
+        assert_eq!(&pixels[..4], &[Mono(0b1100_0000), Mono(0b1111_1111), Mono(0b1101_0010), Mono(0b1111_1111)]);
+    }
+}
@@ -0,0 +1,94 @@
+/// Logical key code delivered with keyboard events.
+/// Printable keys use their ASCII code (letters are uppercase),
+/// all other keys are listed below.
+pub type KeyCode = u16;
+
+pub const UNKNOWN: KeyCode = 0;
+
+pub const SPACE: KeyCode = 0x20;
+pub const KEY_0: KeyCode = 0x30;
+pub const KEY_9: KeyCode = 0x39;
+pub const A: KeyCode = 0x41;
+pub const Z: KeyCode = 0x5a;
+
+pub const ESCAPE: KeyCode = 0x100;
+pub const TAB: KeyCode = 0x101;
+pub const BACKTAB: KeyCode = 0x102;
+pub const BACKSPACE: KeyCode = 0x103;
+pub const RETURN: KeyCode = 0x104;
+pub const ENTER: KeyCode = 0x105;
+pub const INSERT: KeyCode = 0x106;
+pub const DELETE: KeyCode = 0x107;
+pub const PAUSE: KeyCode = 0x108;
+pub const PRINT: KeyCode = 0x109;
+
+pub const HOME: KeyCode = 0x110;
+pub const END: KeyCode = 0x111;
+pub const LEFT: KeyCode = 0x112;
+pub const UP: KeyCode = 0x113;
+pub const RIGHT: KeyCode = 0x114;
+pub const DOWN: KeyCode = 0x115;
+pub const PAGE_UP: KeyCode = 0x116;
+pub const PAGE_DOWN: KeyCode = 0x117;
+
+pub const SHIFT: KeyCode = 0x120;
+pub const CONTROL: KeyCode = 0x121;
+pub const META: KeyCode = 0x122;
+pub const ALT: KeyCode = 0x123;
+pub const CAPS_LOCK: KeyCode = 0x124;
+pub const NUM_LOCK: KeyCode = 0x125;
+pub const SCROLL_LOCK: KeyCode = 0x126;
+
+pub const F1: KeyCode = 0x130;
+pub const F2: KeyCode = 0x131;
+pub const F3: KeyCode = 0x132;
+pub const F4: KeyCode = 0x133;
+pub const F5: KeyCode = 0x134;
+pub const F6: KeyCode = 0x135;
+pub const F7: KeyCode = 0x136;
+pub const F8: KeyCode = 0x137;
+pub const F9: KeyCode = 0x138;
+pub const F10: KeyCode = 0x139;
+pub const F11: KeyCode = 0x13a;
+pub const F12: KeyCode = 0x13b;
+
+#[inline]
+pub fn is_printable(key: KeyCode) -> bool {
+    (0x20..0x7f).contains(&key)
+}
+
+/// Key code of a printable ascii character (letters are case insensitive)
+#[inline]
+pub fn from_ascii(c: u8) -> Option<KeyCode> {
+    let key = c.to_ascii_uppercase() as KeyCode;
+    if is_printable(key) { Some(key) } else { None }
+}
+
+/// Function key `F<n>` for n in 1..=12
+#[inline]
+pub fn function(n: u8) -> Option<KeyCode> {
+    if (1..=12).contains(&n) { Some(F1 + (n - 1) as KeyCode) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_ascii_test() {
+        assert_eq!(from_ascii(b'a'), Some(A));
+        assert_eq!(from_ascii(b'Z'), Some(Z));
+        assert_eq!(from_ascii(b'0'), Some(KEY_0));
+        assert_eq!(from_ascii(b' '), Some(SPACE));
+        assert_eq!(from_ascii(b'\n'), None);
+        assert_eq!(from_ascii(0x7f), None);
+    }
+
+    #[test]
+    fn function_test() {
+        assert_eq!(function(1), Some(F1));
+        assert_eq!(function(12), Some(F12));
+        assert_eq!(function(0), None);
+        assert_eq!(function(13), None);
+    }
+}
@@ -1,14 +1,24 @@
 #![no_std]
-#![feature(maybe_uninit_uninit_array)]
 
 mod sys;
 mod read;
 pub mod utils;
 pub mod event;
+pub mod keys;
 pub mod socket;
 pub mod bufsocket;
 pub mod write;
 pub mod winclient;
+pub mod pixel;
+pub mod convert;
+pub mod canvas;
+pub mod font;
+pub mod blend;
+pub mod decode;
+pub mod dump;
+pub mod shm;
+pub mod compress;
+pub mod delta;
 pub mod time;
 
 pub use sys::io_sys::{
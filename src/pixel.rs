@@ -0,0 +1,220 @@
+use core::{mem::size_of_val, slice};
+
+use crate::winclient::Format;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Pixel type which can be sent to server. Ties memory layout to `Format` code,
+/// implemented only for plain `repr(transparent)` / `repr(C)` byte types of this module
+pub trait Pixel: sealed::Sealed + Copy {
+    const FORMAT: Format;
+    /// Bytes per element
+    const SIZE: u8;
+    /// Pixels packed into one element along a row. Every row starts with new element
+    const PER_ELEMENT: u16 = 1;
+}
+
+/// Count of `P` elements in one row of `w` pixels
+#[inline]
+pub fn row_len<P: Pixel>(w: u16) -> usize {
+    (w as usize).div_ceil(P::PER_ELEMENT as usize)
+}
+
+/// Count of `P` elements in `w` x `h` surface
+#[inline]
+pub fn surface_len<P: Pixel>(w: u16, h: u16) -> usize {
+    row_len::<P>(w) * h as usize
+}
+
+macro_rules! pixel_impl {
+    ($name:ident, $format:expr) => {
+        impl sealed::Sealed for $name {}
+
+        impl Pixel for $name {
+            const FORMAT: Format = $format;
+            const SIZE: u8 = core::mem::size_of::<$name>() as u8;
+        }
+    };
+    ($name:ident, $repr:ty, $format:expr) => {
+        pixel_impl!($name, $format);
+
+        impl From<$repr> for $name {
+            #[inline]
+            fn from(v: $repr) -> Self {
+                Self(v)
+            }
+        }
+    };
+}
+
+/// 8 bit luminance
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Gray8(pub u8);
+pixel_impl!(Gray8, u8, Format::GS);
+
+/// `0xAARRGGBB` in native byte order
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Argb8888(pub u32);
+pixel_impl!(Argb8888, u32, Format::ARGB);
+
+/// Index into VGA palette
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Vga8(pub u8);
+pixel_impl!(Vga8, u8, Format::VGA);
+
+/// `rrrrrggggggbbbbb`, kept little endian so memory matches wire on any host
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rgb565(u16);
+pixel_impl!(Rgb565, Format::RGB565);
+
+impl Rgb565 {
+    /// Pack 8 bit channels dropping low bits
+    #[inline]
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self::from_u16(((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3))
+    }
+
+    #[inline]
+    pub const fn from_u16(v: u16) -> Self {
+        Self(v.to_le())
+    }
+
+    #[inline]
+    pub const fn to_u16(self) -> u16 {
+        u16::from_le(self.0)
+    }
+}
+
+impl From<u16> for Rgb565 {
+    #[inline]
+    fn from(v: u16) -> Self {
+        Self::from_u16(v)
+    }
+}
+
+/// Red, green, blue bytes
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rgb888 {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8
+}
+pixel_impl!(Rgb888, Format::RGB888);
+
+/// Red, green, blue, alpha bytes with color not multiplied by alpha
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rgba8888 {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8
+}
+pixel_impl!(Rgba8888, Format::RGBA);
+
+/// Red, green, blue, alpha bytes with color already multiplied by alpha
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PremulRgba8888 {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8
+}
+pixel_impl!(PremulRgba8888, Format::PRGBA);
+
+impl From<Rgba8888> for PremulRgba8888 {
+    fn from(p: Rgba8888) -> Self {
+        let mul = |c: u8| ((c as u16 * p.a as u16 + 127) / 255) as u8;
+        Self { r: mul(p.r), g: mul(p.g), b: mul(p.b), a: p.a }
+    }
+}
+
+/// Eight 1 bit pixels (1 is white), leftmost in the most significant bit
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Mono(pub u8);
+impl sealed::Sealed for Mono {}
+
+impl From<u8> for Mono {
+    #[inline]
+    fn from(v: u8) -> Self {
+        Self(v)
+    }
+}
+
+impl Pixel for Mono {
+    const FORMAT: Format = Format::MONO;
+    const SIZE: u8 = 1;
+    const PER_ELEMENT: u16 = 8;
+}
+
+impl Mono {
+    /// Pixel `i` in `0..8` (0 is leftmost) of this byte, only low 3 bits of `i` are used
+    #[inline]
+    pub const fn get(self, i: u8) -> bool {
+        self.0 & (0x80 >> (i & 7)) != 0
+    }
+
+    /// Set pixel `i` in `0..8`, only low 3 bits of `i` are used like in `get`
+    #[inline]
+    pub fn set(&mut self, i: u8, white: bool) {
+        if white {
+            self.0 |= 0x80 >> (i & 7);
+        } else {
+            self.0 &= !(0x80 >> (i & 7));
+        }
+    }
+}
+
+/// Raw bytes of `pixels` as they are sent to server
+#[inline]
+pub(crate) fn as_bytes<P: Pixel>(pixels: &[P]) -> &[u8] {
+    // sealed pixel types are bytes or integers without padding
+    unsafe { slice::from_raw_parts(pixels.as_ptr() as *const u8, size_of_val(pixels)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_layout_test() {
+        assert_eq!(as_bytes(&[Rgb565::new(0xff, 0, 0x08)]), &[0x01, 0xf8]);
+        assert_eq!(Rgb565::from_u16(0x1234).to_u16(), 0x1234);
+        assert_eq!(as_bytes(&[Rgb888 { r: 1, g: 2, b: 3 }; 2]), &[1, 2, 3, 1, 2, 3]);
+        assert_eq!(as_bytes(&[Rgba8888 { r: 1, g: 2, b: 3, a: 4 }]), &[1, 2, 3, 4]);
+        assert_eq!(
+            PremulRgba8888::from(Rgba8888 { r: 255, g: 100, b: 0, a: 128 }),
+            PremulRgba8888 { r: 128, g: 50, b: 0, a: 128 }
+        );
+    }
+
+    #[test]
+    fn mono_test() {
+        assert_eq!(row_len::<Mono>(1), 1);
+        assert_eq!(row_len::<Mono>(8), 1);
+        assert_eq!(surface_len::<Mono>(9, 3), 6);
+        assert_eq!(surface_len::<Gray8>(9, 3), 27);
+
+        let mut m = Mono(0);
+        m.set(0, true);
+        m.set(7, true);
+        assert_eq!(m, Mono(0x81));
+        assert!(m.get(7) && !m.get(6));
+        m.set(0, false);
+        assert_eq!(m, Mono(0x01));
+
+        // index wraps within the byte instead of overflowing the shift
+        m.set(9, true);
+        assert_eq!(m, Mono(0x41));
+        assert!(m.get(15) && m.get(255) && !m.get(8));
+    }
+}
@@ -4,7 +4,7 @@ use core::mem::size_of;
 macro_rules! read_impl {
     ($name: ident, $from_bytes: ident, $_type: ty) => {
         pub fn $name(input: &mut &[u8]) -> Option<$_type> {
-            if input.len() <= size_of::<$_type>() {
+            if input.len() >= size_of::<$_type>() {
                 let (int_bytes, rest) = input.split_at(size_of::<$_type>());
                 *input = rest;
                 Some(<$_type>::$from_bytes(int_bytes.try_into().unwrap()))
@@ -0,0 +1,99 @@
+use core::{
+    ffi::c_void,
+    marker::PhantomData,
+    mem::size_of,
+    ptr::null_mut,
+    slice
+};
+
+use crate::{pixel::{self, Pixel}, sys::io_sys};
+
+#[derive(Debug)]
+pub enum CreateError {
+    /// Surface has zero bytes
+    BadSize,
+    MemfdCreate(io_sys::ErrNo),
+    Truncate(io_sys::ErrNo),
+    Seal(io_sys::ErrNo),
+    Map(io_sys::ErrNo),
+}
+
+/// Framebuffer in memfd shared with server. Attach it with `Client::attach_shm`,
+/// then draw into `pixels_mut()` and announce changes with `Client::present_shm`.
+/// Memory is zero initialized, which is valid value of every `Pixel`
+pub struct ShmBuffer<P: Pixel> {
+    fd: io_sys::Fd,
+    ptr: *mut c_void,
+    w: u16,
+    h: u16,
+    _pixel: PhantomData<P>
+}
+
+impl<P: Pixel> ShmBuffer<P> {
+    pub fn new(w: u16, h: u16) -> Result<Self, CreateError> {
+        let len = Self::byte_len(w, h).ok_or(CreateError::BadSize)?;
+
+        let fd = unsafe {
+            io_sys::memfd_create(c"mini_winclient_frame", io_sys::MFD_CLOEXEC | io_sys::MFD_ALLOW_SEALING)
+        }.map_err(CreateError::MemfdCreate)?;
+
+        // size is fixed so server can not get SIGBUS from client shrinking the file
+        let ptr = unsafe {
+            io_sys::ftruncate(fd, len)
+                .map_err(CreateError::Truncate)
+                .and_then(|_| io_sys::fcntl(fd, io_sys::F_ADD_SEALS, io_sys::F_SEAL_SHRINK | io_sys::F_SEAL_GROW)
+                    .map_err(CreateError::Seal))
+                .and_then(|_| io_sys::mmap(null_mut(), len, io_sys::PROT_READ | io_sys::PROT_WRITE, io_sys::MAP_SHARED, fd, 0)
+                    .map_err(CreateError::Map))
+        };
+
+        match ptr {
+            Ok(ptr) => Ok(Self { fd, ptr, w, h, _pixel: PhantomData }),
+            Err(err) => {
+                unsafe { io_sys::close(fd) };
+                Err(err)
+            },
+        }
+    }
+
+    fn byte_len(w: u16, h: u16) -> Option<usize> {
+        pixel::surface_len::<P>(w, h)
+            .checked_mul(size_of::<P>())
+            .filter(|len| *len > 0)
+    }
+
+    #[inline]
+    pub fn w(&self) -> u16 {
+        self.w
+    }
+
+    #[inline]
+    pub fn h(&self) -> u16 {
+        self.h
+    }
+
+    /// Row major pixels, `pixel::surface_len::<P>(w, h)` long
+    #[inline]
+    pub fn pixels(&self) -> &[P] {
+        unsafe { slice::from_raw_parts(self.ptr as *const P, pixel::surface_len::<P>(self.w, self.h)) }
+    }
+
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [P] {
+        unsafe { slice::from_raw_parts_mut(self.ptr as *mut P, pixel::surface_len::<P>(self.w, self.h)) }
+    }
+
+    #[inline]
+    pub(crate) fn fd(&self) -> io_sys::Fd {
+        self.fd
+    }
+}
+
+impl<P: Pixel> Drop for ShmBuffer<P> {
+    fn drop(&mut self) {
+        unsafe {
+            io_sys::munmap(self.ptr, pixel::surface_len::<P>(self.w, self.h) * size_of::<P>());
+            io_sys::close(self.fd)
+        }
+    }
+}
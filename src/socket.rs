@@ -5,12 +5,41 @@ use core::{
     },
     ffi::c_void,
     slice,
-    fmt::Write
+    fmt::{self, Write},
+    time::Duration
 };
 
 use crate::sys::io_sys;
 
 pub const LOCALHOST: [u8; 4] = [ 127, 0, 0, 1 ];
+pub const LOCALHOST_V6: [u8; 16] = [ 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1 ];
+
+/// Address of the server to connect to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Addr<'a> {
+    V4 { ip: [u8; 4], port: u16 },
+    /// `scope_id` is interface index for link-local addresses, 0 otherwise
+    V6 { ip: [u8; 16], port: u16, scope_id: u32 },
+    /// Path of unix domain stream socket
+    Unix(&'a str),
+}
+
+impl<'a> Addr<'a> {
+    #[inline]
+    pub const fn v4(ip: [u8; 4], port: u16) -> Self {
+        Addr::V4 { ip, port }
+    }
+
+    #[inline]
+    pub const fn v6(ip: [u8; 16], port: u16) -> Self {
+        Addr::V6 { ip, port, scope_id: 0 }
+    }
+
+    #[inline]
+    pub const fn unix(path: &'a str) -> Self {
+        Addr::Unix(path)
+    }
+}
 
 #[derive(Debug)]
 pub enum ConnectError {
@@ -22,17 +51,134 @@ pub enum ConnectError {
     NoBufs,
     ProtoNoSupport,
     UnknownCreateErr(io_sys::ErrNo),
+    /// Unix socket path is empty, contains zero bytes or is longer than `io_sys::UNIX_PATH_MAX - 1`
+    BadPath,
 
     Refused,
+    AddrInUse,
+    AddrNotAvail,
+    /// No free local ports (TCP) or listener backlog is full (unix socket)
+    Again,
+    Already,
+    BadFd,
+    Fault,
+    InProgress,
+    IsConn,
+    NetUnreach,
+    NotSock,
+    ProtoType,
+    TimedOut,
+    /// Unix socket path does not exist
+    NotFound,
     UnknownConnectErr(io_sys::ErrNo),
 }
 
+impl ConnectError {
+    fn from_connect_errno(errno: io_sys::ErrNo) -> Self {
+        match errno {
+            io_sys::errno::EACCES | io_sys::errno::EPERM => ConnectError::NoAccess,
+            io_sys::errno::EADDRINUSE => ConnectError::AddrInUse,
+            io_sys::errno::EADDRNOTAVAIL => ConnectError::AddrNotAvail,
+            io_sys::errno::EAFNOSUPPORT => ConnectError::AfNoSupport,
+            io_sys::errno::EAGAIN => ConnectError::Again,
+            io_sys::errno::EALREADY => ConnectError::Already,
+            io_sys::errno::EBADF => ConnectError::BadFd,
+            io_sys::errno::ECONNREFUSED => ConnectError::Refused,
+            io_sys::errno::EFAULT => ConnectError::Fault,
+            io_sys::errno::EINPROGRESS => ConnectError::InProgress,
+            io_sys::errno::EISCONN => ConnectError::IsConn,
+            io_sys::errno::ENETUNREACH => ConnectError::NetUnreach,
+            io_sys::errno::ENOTSOCK => ConnectError::NotSock,
+            io_sys::errno::EPROTOTYPE => ConnectError::ProtoType,
+            io_sys::errno::ETIMEDOUT => ConnectError::TimedOut,
+            io_sys::errno::ENOENT | io_sys::errno::ENOTDIR => ConnectError::NotFound,
+            i => ConnectError::UnknownConnectErr(i)
+        }
+    }
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::NoAccess => write!(f, "permission denied"),
+            ConnectError::AfNoSupport => write!(f, "address family not supported"),
+            ConnectError::Invalid => write!(f, "invalid socket arguments"),
+            ConnectError::MFile => write!(f, "too many open files in process"),
+            ConnectError::NFile => write!(f, "too many open files in system"),
+            ConnectError::NoBufs => write!(f, "not enough memory for socket"),
+            ConnectError::ProtoNoSupport => write!(f, "protocol not supported"),
+            ConnectError::UnknownCreateErr(errno) => write!(f, "failed to create socket (errno {})", errno),
+            ConnectError::BadPath => write!(f, "invalid unix socket path"),
+            ConnectError::Refused => write!(f, "connection refused"),
+            ConnectError::AddrInUse => write!(f, "local address already in use"),
+            ConnectError::AddrNotAvail => write!(f, "address not available"),
+            ConnectError::Again => write!(f, "no free local ports or server backlog full"),
+            ConnectError::Already => write!(f, "connection attempt already in progress"),
+            ConnectError::BadFd => write!(f, "bad file descriptor"),
+            ConnectError::Fault => write!(f, "address points outside of address space"),
+            ConnectError::InProgress => write!(f, "connection in progress"),
+            ConnectError::IsConn => write!(f, "socket already connected"),
+            ConnectError::NetUnreach => write!(f, "network unreachable"),
+            ConnectError::NotSock => write!(f, "descriptor is not a socket"),
+            ConnectError::ProtoType => write!(f, "socket type does not support protocol"),
+            ConnectError::TimedOut => write!(f, "connection timed out"),
+            ConnectError::NotFound => write!(f, "no socket at given path"),
+            ConnectError::UnknownConnectErr(errno) => write!(f, "failed to connect (errno {})", errno),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ReadError {
     Again,
+    /// Peer closed connection
+    Eof,
+    BadFd,
+    Fault,
+    Invalid,
+    Io,
+    IsDir,
+    ConnReset,
+    NotConn,
+    TimedOut,
     Unknown(io_sys::ErrNo),
 }
 
+impl From<io_sys::ErrNo> for ReadError {
+    fn from(value: io_sys::ErrNo) -> Self {
+        match value {
+            io_sys::errno::EAGAIN => ReadError::Again,
+            io_sys::errno::EBADF => ReadError::BadFd,
+            io_sys::errno::EFAULT => ReadError::Fault,
+            io_sys::errno::EINVAL => ReadError::Invalid,
+            io_sys::errno::EIO => ReadError::Io,
+            io_sys::errno::EISDIR => ReadError::IsDir,
+            io_sys::errno::ECONNRESET => ReadError::ConnReset,
+            io_sys::errno::ENOTCONN => ReadError::NotConn,
+            io_sys::errno::ETIMEDOUT => ReadError::TimedOut,
+            other => ReadError::Unknown(other)
+        }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Again => write!(f, "no data available"),
+            ReadError::Eof => write!(f, "connection closed by peer"),
+            ReadError::BadFd => write!(f, "bad file descriptor"),
+            ReadError::Fault => write!(f, "buffer outside of address space"),
+            ReadError::Invalid => write!(f, "descriptor is not readable"),
+            ReadError::Io => write!(f, "i/o error"),
+            ReadError::IsDir => write!(f, "descriptor is a directory"),
+            ReadError::ConnReset => write!(f, "connection reset by peer"),
+            ReadError::NotConn => write!(f, "socket is not connected"),
+            ReadError::TimedOut => write!(f, "connection timed out"),
+            ReadError::Unknown(errno) => write!(f, "read failed (errno {})", errno),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum FlagsChangeError {
     BadFd,
@@ -50,12 +196,93 @@ impl From<io_sys::ErrNo> for FlagsChangeError {
     }
 }
 
+impl fmt::Display for FlagsChangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlagsChangeError::BadFd => write!(f, "bad file descriptor"),
+            FlagsChangeError::Interrupted => write!(f, "interrupted by signal"),
+            FlagsChangeError::Unknown(errno) => write!(f, "fcntl failed (errno {})", errno),
+        }
+    }
+}
+
 
 #[derive(Debug)]
 pub enum WriteError {
+    /// Socket is in non blocking mode and send buffer is full
+    WouldBlock,
+    BadFd,
+    DestAddrReq,
+    DQuot,
+    Fault,
+    FBig,
+    Invalid,
+    Io,
+    NoSpc,
+    Perm,
+    /// Peer closed connection
+    Pipe,
+    ConnReset,
     Unknown(io_sys::ErrNo),
 }
 
+impl From<io_sys::ErrNo> for WriteError {
+    fn from(value: io_sys::ErrNo) -> Self {
+        match value {
+            io_sys::errno::EAGAIN => WriteError::WouldBlock,
+            io_sys::errno::EBADF => WriteError::BadFd,
+            io_sys::errno::EDESTADDRREQ => WriteError::DestAddrReq,
+            io_sys::errno::EDQUOT => WriteError::DQuot,
+            io_sys::errno::EFAULT => WriteError::Fault,
+            io_sys::errno::EFBIG => WriteError::FBig,
+            io_sys::errno::EINVAL => WriteError::Invalid,
+            io_sys::errno::EIO => WriteError::Io,
+            io_sys::errno::ENOSPC => WriteError::NoSpc,
+            io_sys::errno::EPERM => WriteError::Perm,
+            io_sys::errno::EPIPE => WriteError::Pipe,
+            io_sys::errno::ECONNRESET => WriteError::ConnReset,
+            other => WriteError::Unknown(other)
+        }
+    }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::WouldBlock => write!(f, "send buffer is full"),
+            WriteError::BadFd => write!(f, "bad file descriptor"),
+            WriteError::DestAddrReq => write!(f, "destination address required"),
+            WriteError::DQuot => write!(f, "disk quota exhausted"),
+            WriteError::Fault => write!(f, "buffer outside of address space"),
+            WriteError::FBig => write!(f, "file too big"),
+            WriteError::Invalid => write!(f, "descriptor is not writable"),
+            WriteError::Io => write!(f, "i/o error"),
+            WriteError::NoSpc => write!(f, "no space left on device"),
+            WriteError::Perm => write!(f, "operation not permitted"),
+            WriteError::Pipe => write!(f, "connection closed by peer"),
+            WriteError::ConnReset => write!(f, "connection reset by peer"),
+            WriteError::Unknown(errno) => write!(f, "write failed (errno {})", errno),
+        }
+    }
+}
+
+/// Max count of slices passed to one `writev`
+pub const MAX_IOVECS: usize = 8;
+
+/// Skip `count` bytes from the front of `bufs` dropping slices that became empty
+fn advance_slices(bufs: &mut &mut [&[u8]], mut count: usize) {
+    while let Some(first) = bufs.first() {
+        if first.len() > count {
+            break;
+        }
+        count -= first.len();
+        *bufs = &mut core::mem::take(bufs)[1..];
+    }
+    if let Some(first) = bufs.first_mut() {
+        *first = &first[count..];
+    }
+}
+
 pub struct Socket {
     fd: io_sys::Fd
 }
@@ -79,44 +306,48 @@ impl<'s, const BUF_SIZE: usize> SocketIter<'s, BUF_SIZE> {
 }
 
 impl Socket {
-    pub fn connect(ip: [u8; 4], port: u16) -> Result<Self, ConnectError> {
+    pub fn connect(addr: Addr) -> Result<Self, ConnectError> {
+        match addr {
+            Addr::V4 { ip, port } => {
+                let addr = io_sys::SockAddrIn::new(port, ip);
+                unsafe {
+                    Self::open(io_sys::AF_INET, io_sys::IPPROTO_TCP, &addr as *const _ as *const c_void, size_of::<io_sys::SockAddrIn>())
+                }
+            },
+            Addr::V6 { ip, port, scope_id } => {
+                let addr = io_sys::SockAddrIn6::new(port, ip, scope_id);
+                unsafe {
+                    Self::open(io_sys::AF_INET6, io_sys::IPPROTO_TCP, &addr as *const _ as *const c_void, size_of::<io_sys::SockAddrIn6>())
+                }
+            },
+            Addr::Unix(path) => {
+                let addr = io_sys::SockAddrUn::new(path.as_bytes())
+                    .ok_or(ConnectError::BadPath)?;
+                unsafe {
+                    Self::open(io_sys::AF_UNIX, 0, &addr as *const _ as *const c_void, size_of::<io_sys::SockAddrUn>())
+                }
+            },
+        }
+    }
+
+    /// Connect to unix domain stream socket at `path`
+    #[inline]
+    pub fn connect_unix(path: &str) -> Result<Self, ConnectError> {
+        Self::connect(Addr::Unix(path))
+    }
+
+    unsafe fn open(family: usize, protocol: usize, addr: *const c_void, addrlen: usize) -> Result<Self, ConnectError> {
         match unsafe {
-            io_sys::socket(io_sys::AF_INET, io_sys::SOCK_STREAM, io_sys::IPPROTO_TCP)
+            io_sys::socket(family, io_sys::SOCK_STREAM, protocol)
         } {
-            Ok(fd) => {
-                let addr = io_sys::SockAddr::new(
-                    io_sys::AF_INET,
-                    port,
-                    ip
-                );
-
-                match unsafe {
-                    io_sys::connect(fd, &addr)
-                } {
-                    Ok(_) => Ok(Self { fd: fd }),
-                    Err(errno) => {
-                        unsafe { io_sys::close(fd) };
-                        match errno {
-                            io_sys::errno::EACCES => todo!(),
-                            io_sys::errno::EADDRINUSE => todo!(),
-                            io_sys::errno::EADDRNOTAVAIL => todo!(),
-                            io_sys::errno::EAFNOSUPPORT => todo!(),
-                            io_sys::errno::EAGAIN => todo!(),
-                            io_sys::errno::EALREADY => todo!(),
-                            io_sys::errno::EBADF => todo!(),
-                            io_sys::errno::ECONNREFUSED => Err(ConnectError::Refused),
-                            io_sys::errno::EFAULT => todo!(),
-                            io_sys::errno::EINPROGRESS => todo!(),
-                            io_sys::errno::EINTR => todo!(),
-                            io_sys::errno::EISCONN => todo!(),
-                            io_sys::errno::ENETUNREACH => todo!(),
-                            io_sys::errno::ENOTSOCK => todo!(),
-                            io_sys::errno::EPROTOTYPE => todo!(),
-                            io_sys::errno::ETIMEDOUT => todo!(),
-                            i => Err(ConnectError::UnknownConnectErr(i))
-                        }
-                    },
-                }
+            Ok(fd) => match unsafe {
+                Self::connect_fd(fd, addr, addrlen)
+            } {
+                Ok(_) => Ok(Self { fd }),
+                Err(errno) => {
+                    unsafe { io_sys::close(fd) };
+                    Err(ConnectError::from_connect_errno(errno))
+                },
             },
             Err(errno) => match errno {
                 io_sys::errno::EACCES => Err(ConnectError::NoAccess),
@@ -131,6 +362,29 @@ impl Socket {
         }
     }
 
+    /// Connect retrying on EINTR. Interrupted connect keeps going in background,
+    /// so wait for it to finish and fetch its result
+    unsafe fn connect_fd(fd: io_sys::Fd, addr: *const c_void, addrlen: usize) -> Result<(), io_sys::ErrNo> {
+        match io_sys::connect(fd, addr, addrlen) {
+            Err(io_sys::errno::EINTR) => {
+                let mut pfd = io_sys::PollFd::new(fd, io_sys::POLLOUT);
+                loop {
+                    match io_sys::poll(&mut pfd, 1, -1) {
+                        Err(io_sys::errno::EINTR) => continue,
+                        Err(errno) => return Err(errno),
+                        Ok(_) => break,
+                    }
+                }
+
+                let mut err: i32 = 0;
+                let mut len = size_of::<i32>() as u32;
+                io_sys::getsockopt(fd, io_sys::SOL_SOCKET, io_sys::SO_ERROR, &mut err as *mut i32 as *mut c_void, &mut len)?;
+                if err == 0 { Ok(()) } else { Err(err as io_sys::ErrNo) }
+            },
+            result => result
+        }
+    }
+
     pub fn set_non_blocking_mode(&mut self, nbm: bool) -> Result<(), FlagsChangeError> {
         unsafe {
             let mut flags = io_sys::fcntl(self.fd, io_sys::F_GETFL, 0)
@@ -149,28 +403,63 @@ impl Socket {
     }
 
     pub fn write_bytes(&mut self, b: &[u8]) -> Result<usize, WriteError> {
-        match unsafe {
-            io_sys::write(self.fd, b.as_ptr() as *const c_void, b.len())
-        } {
-            Ok(bytes) => Ok(bytes),
-            Err(errno) => match errno {
-                io_sys::errno::EAGAIN => todo!(),
-                io_sys::errno::EBADF => todo!(),
-                io_sys::errno::EDESTADDRREQ => todo!(),
-                io_sys::errno::EDQUOT => todo!(),
-                io_sys::errno::EFAULT => todo!(),
-                io_sys::errno::EFBIG => todo!(),
-                io_sys::errno::EINTR => todo!(),
-                io_sys::errno::EINVAL => todo!(),
-                io_sys::errno::EIO => todo!(),
-                io_sys::errno::ENOSPC => todo!(),
-                io_sys::errno::EPERM => todo!(),
-                io_sys::errno::EPIPE => todo!(),
-                i => Err(WriteError::Unknown(i))
-            },
+        self.write_vectored(&[b])
+    }
+
+    /// Write `bufs` in order with single syscall. Max `MAX_IOVECS` slices are written at once.
+    /// Uses `sendmsg` with `MSG_NOSIGNAL` so closed peer is reported as `WriteError::Pipe` instead of SIGPIPE
+    pub fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<usize, WriteError> {
+        self.send_vectored(bufs, None)
+    }
+
+    /// Same as `write_vectored` but also pass duplicate of descriptor `fd` to peer (unix sockets only).
+    /// Descriptor arrives together with first written byte, so on short write the rest must be sent with `write_all_vectored`
+    pub fn write_vectored_with_fd(&mut self, bufs: &[&[u8]], fd: io_sys::Fd) -> Result<usize, WriteError> {
+        self.send_vectored(bufs, Some(io_sys::FdControl::new(fd)))
+    }
+
+    fn send_vectored(&mut self, bufs: &[&[u8]], mut control: Option<io_sys::FdControl>) -> Result<usize, WriteError> {
+        let mut iov: [MaybeUninit<io_sys::IoVec>; MAX_IOVECS] = [const { MaybeUninit::uninit() }; MAX_IOVECS];
+        let count = bufs.len().min(MAX_IOVECS);
+        for i in 0..count {
+            iov[i].write(io_sys::IoVec::new(bufs[i]));
+        }
+        let iov = iov.as_ptr() as *const io_sys::IoVec;
+        let msg = match &mut control {
+            Some(control) => io_sys::MsgHdr::with_control(
+                iov,
+                count,
+                control as *mut io_sys::FdControl as *mut c_void,
+                size_of::<io_sys::FdControl>()
+            ),
+            None => io_sys::MsgHdr::new(iov, count),
+        };
+        loop {
+            match unsafe {
+                io_sys::sendmsg(self.fd, &msg, io_sys::MSG_NOSIGNAL)
+            } {
+                Ok(bytes) => return Ok(bytes),
+                Err(io_sys::errno::EINTR) => continue,
+                Err(errno) => return Err(WriteError::from(errno)),
+            }
         }
     }
 
+    /// Write whole `b` retrying on short writes.
+    /// In non blocking mode `WriteError::WouldBlock` may be returned after part of data was written
+    pub fn write_all(&mut self, b: &[u8]) -> Result<(), WriteError> {
+        self.write_all_vectored(&mut [b])
+    }
+
+    /// Write all `bufs` in order retrying on short writes. Slices in `bufs` are advanced past written data
+    pub fn write_all_vectored(&mut self, mut bufs: &mut [&[u8]]) -> Result<(), WriteError> {
+        advance_slices(&mut bufs, 0);
+        while !bufs.is_empty() {
+            let written = self.write_vectored(bufs)?;
+            advance_slices(&mut bufs, written);
+        }
+        Ok(())
+    }
 
     pub fn write_transmuted<T: Sized>(&mut self, mut val: T) -> Result<usize, WriteError> {
         let sl = unsafe {
@@ -192,20 +481,42 @@ impl Socket {
     }
 
     pub fn read_bytes(&mut self, b: &mut[u8]) -> Result<usize, ReadError> {
-        match unsafe {
-            io_sys::read(self.fd, b.as_mut_ptr() as *mut c_void, b.len())
-        } {
-            Ok(bytes) => Ok(bytes),
-            Err(errno) => match errno {
-                io_sys::errno::EAGAIN => Err(ReadError::Again),
-                io_sys::errno::EBADF => todo!(),
-                io_sys::errno::EFAULT => todo!(),
-                io_sys::errno::EINTR => todo!(),
-                io_sys::errno::EINVAL => todo!(),
-                io_sys::errno::EIO => todo!(),
-                io_sys::errno::EISDIR => todo!(),
-                i => Err(ReadError::Unknown(i))
-            },
+        unsafe { self.read_raw(b.as_mut_ptr(), b.len()) }
+    }
+
+    /// Read into possibly uninitialized memory. Returned count of bytes at the front of `b` become initialized
+    pub fn read_uninit(&mut self, b: &mut[MaybeUninit<u8>]) -> Result<usize, ReadError> {
+        unsafe { self.read_raw(b.as_mut_ptr() as *mut u8, b.len()) }
+    }
+
+    unsafe fn read_raw(&mut self, b: *mut u8, len: usize) -> Result<usize, ReadError> {
+        loop {
+            match unsafe {
+                io_sys::read(self.fd, b as *mut c_void, len)
+            } {
+                Ok(0) if len > 0 => return Err(ReadError::Eof),
+                Ok(bytes) => return Ok(bytes),
+                Err(io_sys::errno::EINTR) => continue,
+                Err(errno) => return Err(ReadError::from(errno)),
+            }
+        }
+    }
+
+    /// Block until socket has data to read (or peer hung up) or `timeout` expires.
+    /// Returns false on timeout
+    pub fn wait_readable(&mut self, timeout: Duration) -> Result<bool, ReadError> {
+        // round up to not spin with zero timeout on sub millisecond remainders
+        let timeout_ms = timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32;
+        let mut pfd = io_sys::PollFd::new(self.fd, io_sys::POLLIN);
+        loop {
+            match unsafe {
+                io_sys::poll(&mut pfd, 1, timeout_ms)
+            } {
+                Ok(count) => return Ok(count > 0),
+                // may wait longer than `timeout` in total, callers keep their own deadline
+                Err(io_sys::errno::EINTR) => continue,
+                Err(errno) => return Err(ReadError::from(errno)),
+            }
         }
     }
 
@@ -233,7 +544,8 @@ impl<'s, const BUF_SIZE: usize> Iterator for SocketIter<'s, BUF_SIZE> {
                     self.count = count;
                     self.i = 0;
                 }
-                Err(_) => todo!(),
+                // iterator has no way to report errors, so any failure ends it
+                Err(_) => return None,
             }
         }
 
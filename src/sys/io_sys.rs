@@ -1,13 +1,17 @@
-use core::{ffi::c_void, mem::{transmute, size_of}};
+use core::{ffi::{c_void, CStr}, mem::transmute, ptr::null_mut};
 
 use super::syscall::{
     nums::{
         SYS_CLOSE,
         SYS_WRITE,
         SYS_READ,
+        SYS_OPEN,
         SYS_SOCKET,
-        SYS_CONNECT, SYS_FCNTL
-    }, __mini_wc_syscall3__, __mini_wc_syscall1__,
+        SYS_CONNECT, SYS_FCNTL,
+        SYS_POLL,
+        SYS_SENDMSG, SYS_GETSOCKOPT,
+        SYS_MMAP, SYS_MUNMAP, SYS_FTRUNCATE, SYS_MEMFD_CREATE
+    }, __mini_wc_syscall3__, __mini_wc_syscall1__, __mini_wc_syscall2__, __mini_wc_syscall5__, __mini_wc_syscall6__,
 };
 
 pub type ErrNo = usize;
@@ -17,6 +21,7 @@ pub mod errno {
     use super::ErrNo;
 
     pub const EPERM: ErrNo = 1;
+    pub const ENOENT: ErrNo = 2;
 
     pub const EINTR: ErrNo = 4;
     pub const EIO: ErrNo = 5;
@@ -28,6 +33,7 @@ pub mod errno {
     pub const EACCES: ErrNo = 13;
     pub const EFAULT: ErrNo = 14;
 
+    pub const ENOTDIR: ErrNo = 20;
     pub const EISDIR: ErrNo = 21;
     pub const EINVAL: ErrNo = 22;
     pub const ENFILE: ErrNo = 23;
@@ -51,8 +57,10 @@ pub mod errno {
 
     pub const ENETUNREACH: ErrNo = 101;
 
+    pub const ECONNRESET: ErrNo = 104;
     pub const ENOBUFS: ErrNo = 105;
     pub const EISCONN: ErrNo = 106;
+    pub const ENOTCONN: ErrNo = 107;
 
     pub const ETIMEDOUT: ErrNo = 110;
     pub const ECONNREFUSED: ErrNo = 111;
@@ -87,6 +95,25 @@ unsafe fn separate_fd(val: *mut c_void) -> Result<Fd, ErrNo> {
     }
 }
 
+/// Open for writing only.
+pub static O_WRONLY: usize = 0o1;
+/// Create file if it does not exist.
+pub static O_CREAT: usize = 0o100;
+/// Truncate existing file to zero length.
+pub static O_TRUNC: usize = 0o1000;
+/// Close descriptor on exec.
+pub static O_CLOEXEC: usize = 0o2000000;
+
+/// Open file by path, `mode` is permission bits of created file
+pub unsafe fn open(path: &CStr, flags: usize, mode: usize) -> Result<Fd, ErrNo> {
+    separate_fd(__mini_wc_syscall3__(
+        SYS_OPEN,
+        path.as_ptr() as *mut c_void,
+        flags as *mut c_void,
+        mode as *mut c_void
+    ))
+}
+
 /// Close file or socket by descriptor
 pub unsafe fn close(fd: usize) {
     __mini_wc_syscall1__(SYS_CLOSE, transmute(fd));
@@ -101,6 +128,18 @@ pub unsafe fn write(fd: usize, data: * const c_void, nbytes: usize) -> Result<us
     ))
 }
 
+#[repr(C)]
+pub struct IoVec {
+    pub base: *const c_void,
+    pub len: usize,
+}
+
+impl IoVec {
+    pub fn new(b: &[u8]) -> Self {
+        Self { base: b.as_ptr() as *const c_void, len: b.len() }
+    }
+}
+
 pub unsafe fn read(fd: usize, data: *mut c_void, nbytes: usize) -> Result<usize, ErrNo> {
     separate_usize(__mini_wc_syscall3__(
         SYS_READ,
@@ -119,10 +158,178 @@ pub unsafe fn fcntl(fd: usize, cmd: usize, arg: usize) -> Result<usize, ErrNo> {
     ))
 }
 
+pub unsafe fn poll(fds: *mut PollFd, nfds: usize, timeout_ms: i32) -> Result<usize, ErrNo> {
+    separate_usize(__mini_wc_syscall3__(
+        SYS_POLL,
+        fds as *mut c_void,
+        nfds as *mut c_void,
+        timeout_ms as isize as *mut c_void
+    ))
+}
+
+/// There is data to read.
+pub static POLLIN: i16 = 0x001;
+/// Writing now will not block.
+pub static POLLOUT: i16 = 0x004;
+
+#[repr(C)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+impl PollFd {
+    pub fn new(fd: Fd, events: i16) -> Self {
+        Self { fd: fd as i32, events, revents: 0 }
+    }
+}
+
+#[repr(C)]
+pub struct MsgHdr {
+    pub name: *mut c_void,
+    pub namelen: u32,
+    pub iov: *const IoVec,
+    pub iovlen: usize,
+    pub control: *mut c_void,
+    pub controllen: usize,
+    pub flags: i32,
+}
+
+impl MsgHdr {
+    /// Message without address and ancillary data
+    pub fn new(iov: *const IoVec, iovlen: usize) -> Self {
+        Self {
+            name: null_mut(),
+            namelen: 0,
+            iov,
+            iovlen,
+            control: null_mut(),
+            controllen: 0,
+            flags: 0
+        }
+    }
+
+    /// Message carrying ancillary data in `control` of `controllen` bytes
+    pub fn with_control(iov: *const IoVec, iovlen: usize, control: *mut c_void, controllen: usize) -> Self {
+        Self { control, controllen, ..Self::new(iov, iovlen) }
+    }
+}
+
+#[repr(C)]
+pub struct CMsgHdr {
+    pub len: usize,
+    pub level: i32,
+    pub _type: i32,
+}
+
+/// Ancillary data contains file descriptors.
+pub static SCM_RIGHTS: i32 = 1;
+
+/// Control message passing single file descriptor. Size is `CMSG_SPACE(sizeof(int))`
+#[repr(C, align(8))]
+pub struct FdControl {
+    hdr: CMsgHdr,
+    fd: i32,
+    pad: u32,
+}
+
+impl FdControl {
+    pub fn new(fd: Fd) -> Self {
+        Self {
+            hdr: CMsgHdr {
+                // CMSG_LEN(sizeof(int))
+                len: size_of::<CMsgHdr>() + size_of::<i32>(),
+                level: SOL_SOCKET as i32,
+                _type: SCM_RIGHTS
+            },
+            fd: fd as i32,
+            pad: 0
+        }
+    }
+}
+
+/// Do not generate SIGPIPE when peer closed connection.
+pub static MSG_NOSIGNAL: usize = 0x4000;
+
+pub unsafe fn sendmsg(sockfd: usize, msg: *const MsgHdr, flags: usize) -> Result<usize, ErrNo> {
+    separate_usize(__mini_wc_syscall3__(
+        SYS_SENDMSG,
+        sockfd as *mut c_void,
+        msg as *mut c_void,
+        flags as *mut c_void
+    ))
+}
+
+pub static SOL_SOCKET: usize = 1;
+/// Get and clear pending socket error.
+pub static SO_ERROR: usize = 4;
+
+pub unsafe fn getsockopt(sockfd: usize, level: usize, optname: usize, optval: *mut c_void, optlen: *mut u32) -> Result<(), ErrNo> {
+    separate_unit(__mini_wc_syscall5__(
+        SYS_GETSOCKOPT,
+        sockfd as *mut c_void,
+        level as *mut c_void,
+        optname as *mut c_void,
+        optval,
+        optlen as *mut c_void
+    ))
+}
+
+/// Close descriptor on exec.
+pub static MFD_CLOEXEC: usize = 0x0001;
+/// Allow `F_ADD_SEALS` on memfd.
+pub static MFD_ALLOW_SEALING: usize = 0x0002;
+
+pub unsafe fn memfd_create(name: &CStr, flags: usize) -> Result<Fd, ErrNo> {
+    separate_fd(__mini_wc_syscall2__(
+        SYS_MEMFD_CREATE,
+        name.as_ptr() as *mut c_void,
+        flags as *mut c_void
+    ))
+}
+
+pub unsafe fn ftruncate(fd: Fd, len: usize) -> Result<(), ErrNo> {
+    separate_unit(__mini_wc_syscall2__(
+        SYS_FTRUNCATE,
+        fd as *mut c_void,
+        len as *mut c_void
+    ))
+}
+
+pub static PROT_READ: usize = 0x1;
+pub static PROT_WRITE: usize = 0x2;
+/// Changes are visible to other mappings of the same file.
+pub static MAP_SHARED: usize = 0x01;
+
+pub unsafe fn mmap(addr: *mut c_void, len: usize, prot: usize, flags: usize, fd: Fd, offset: usize) -> Result<*mut c_void, ErrNo> {
+    let val = __mini_wc_syscall6__(
+        SYS_MMAP,
+        addr,
+        len as *mut c_void,
+        prot as *mut c_void,
+        flags as *mut c_void,
+        fd as *mut c_void,
+        offset as *mut c_void
+    );
+    // valid addresses may have high bit set, errors are in [-4095, -1]
+    if (val as usize) < (-4095isize) as usize {
+        Ok(val)
+    } else {
+        Err((-(val as isize)) as ErrNo)
+    }
+}
+
+pub unsafe fn munmap(addr: *mut c_void, len: usize) {
+    __mini_wc_syscall2__(SYS_MUNMAP, addr, len as *mut c_void);
+}
+
 pub static STDOUT: usize = 1;
 pub static STDERR: usize = 2;
 
+pub static AF_UNIX: usize = 1;
 pub static AF_INET: usize = 2;
+pub static AF_INET6: usize = 10;
 pub static SOCK_STREAM: usize = 1;
 pub static IPPROTO_TCP: usize = 6;
 
@@ -136,9 +343,16 @@ pub static F_SETFD: usize = 2;
 pub static F_GETFL: usize = 3;
 /// Set file status flags.
 pub static F_SETFL: usize = 4;
+/// Add seals to memfd.
+pub static F_ADD_SEALS: usize = 1033;
+
+/// Prevent file from shrinking.
+pub static F_SEAL_SHRINK: usize = 0x0002;
+/// Prevent file from growing.
+pub static F_SEAL_GROW: usize = 0x0004;
 
 pub mod bits {
-    pub static O_NONBLOCK: usize = 04000;
+    pub static O_NONBLOCK: usize = 0o4000;
 }
 
 fn flip16(v: u16) -> u16 {
@@ -146,7 +360,7 @@ fn flip16(v: u16) -> u16 {
 }
 
 #[repr(C)]
-pub struct SockAddr {
+pub struct SockAddrIn {
     family: u16,
     /// NOTE: this is big endian
     port: u16,
@@ -155,16 +369,62 @@ pub struct SockAddr {
     zero: [u8; 8],
 }
 
-impl SockAddr {
-    pub fn new(family: usize, port: u16, ip: [u8; 4]) -> Self {
+impl SockAddrIn {
+    pub fn new(port: u16, ip: [u8; 4]) -> Self {
         Self {
-            family: family as u16,
+            family: AF_INET as u16,
             port: flip16(port),
             addr: unsafe { transmute(ip) },
             zero: [0; 8]
         }
     }
+}
+
+#[repr(C)]
+pub struct SockAddrIn6 {
+    family: u16,
+    /// NOTE: this is big endian
+    port: u16,
+    flowinfo: u32,
+    /// NOTE: bytes in network order
+    addr: [u8; 16],
+    scope_id: u32,
+}
+
+impl SockAddrIn6 {
+    pub fn new(port: u16, ip: [u8; 16], scope_id: u32) -> Self {
+        Self {
+            family: AF_INET6 as u16,
+            port: flip16(port),
+            flowinfo: 0,
+            addr: ip,
+            scope_id
+        }
+    }
+}
+
+/// Max length of `SockAddrUn::path` including terminating zero
+pub const UNIX_PATH_MAX: usize = 108;
+
+#[repr(C)]
+pub struct SockAddrUn {
+    family: u16,
+    path: [u8; UNIX_PATH_MAX],
+}
 
+impl SockAddrUn {
+    /// `None` if `path` is empty, contains zero bytes or does not fit with terminating zero
+    pub fn new(path: &[u8]) -> Option<Self> {
+        if path.is_empty() || path.len() >= UNIX_PATH_MAX || path.contains(&0) {
+            return None
+        }
+        let mut addr = Self {
+            family: AF_UNIX as u16,
+            path: [0; UNIX_PATH_MAX]
+        };
+        addr.path[..path.len()].copy_from_slice(path);
+        Some(addr)
+    }
 }
 
 #[cfg(target_arch = "x86")]
@@ -204,13 +464,14 @@ pub unsafe fn socket(family: usize, _type: usize, protocol: usize) -> Result<Fd,
     }
 }
 
-pub unsafe fn connect(sockfd: usize, addr: *const SockAddr) -> Result<(), ErrNo> {
+/// `addr` points to `SockAddrIn`, `SockAddrIn6` or `SockAddrUn` of `addrlen` bytes
+pub unsafe fn connect(sockfd: usize, addr: *const c_void, addrlen: usize) -> Result<(), ErrNo> {
     #[cfg(target_arch = "x86")]
     {
         void* args[3];
         args[0] = transmute(sockfd as usize);
         args[1] = addr as *mut c_void;
-        args[2] = transmute(sizeof(sockaddr_in));
+        args[2] = transmute(addrlen);
 
         socketcall(SYS_CONNECT, args);
     }
@@ -220,7 +481,7 @@ pub unsafe fn connect(sockfd: usize, addr: *const SockAddr) -> Result<(), ErrNo>
             SYS_CONNECT,
             transmute(sockfd),
             addr as *mut c_void,
-            transmute(size_of::<SockAddr>())
+            transmute(addrlen)
         ))
     }
 }
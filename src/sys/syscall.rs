@@ -5,6 +5,7 @@ use core::{arch::global_asm, ffi::c_void};
 pub mod nums {
     pub static SYS_READ         : usize = 3;
     pub static SYS_WRITE        : usize = 4;
+    pub static SYS_OPEN         : usize = 5;
     pub static SYS_CLOSE        : usize = 6;
     pub static SYS_EXIT         : usize = 1;
     pub static SYS_SOCKETCALL   : usize = 102;
@@ -14,11 +15,19 @@ pub mod nums {
 pub mod nums {
     pub static SYS_READ         : usize = 0;
     pub static SYS_WRITE        : usize = 1;
+    pub static SYS_OPEN         : usize = 2;
     pub static SYS_CLOSE        : usize = 3;
+    pub static SYS_POLL         : usize = 7;
+    pub static SYS_MMAP         : usize = 9;
+    pub static SYS_MUNMAP       : usize = 11;
     pub static SYS_SOCKET       : usize = 41;
     pub static SYS_CONNECT      : usize = 42;
+    pub static SYS_SENDMSG      : usize = 46;
+    pub static SYS_GETSOCKOPT   : usize = 55;
     pub static SYS_FCNTL        : usize = 72;
+    pub static SYS_FTRUNCATE    : usize = 77;
     pub static SYS_GETTIMEOFDAY : usize = 96;
+    pub static SYS_MEMFD_CREATE : usize = 319;
 }
 
 #[cfg(target_arch = "x86")]
@@ -45,4 +54,23 @@ extern "C" {
         arg2: *mut c_void,
         arg3: *mut c_void
     ) -> *mut c_void;
+
+    pub fn __mini_wc_syscall5__(
+        number: usize,
+        arg1: *mut c_void,
+        arg2: *mut c_void,
+        arg3: *mut c_void,
+        arg4: *mut c_void,
+        arg5: *mut c_void
+    ) -> *mut c_void;
+
+    pub fn __mini_wc_syscall6__(
+        number: usize,
+        arg1: *mut c_void,
+        arg2: *mut c_void,
+        arg3: *mut c_void,
+        arg4: *mut c_void,
+        arg5: *mut c_void,
+        arg6: *mut c_void
+    ) -> *mut c_void;
 }
@@ -7,6 +7,7 @@
     .globl __mini_wc_syscall3__
     .globl __mini_wc_syscall4__
     .globl __mini_wc_syscall5__
+    .globl __mini_wc_syscall6__
     .globl __mini_wc_exit__
 
 __mini_wc_syscall1__:
@@ -49,6 +50,17 @@ __mini_wc_syscall5__:
     syscall
     ret
 
+__mini_wc_syscall6__:
+    mov rax, rdi
+    mov rdi, rsi
+    mov rsi, rdx
+    mov rdx, rcx
+    mov r10, r8
+    mov r8, r9
+    mov r9, [rsp + 8]
+    syscall
+    ret
+
 __mini_wc_exit__:
     mov rbx, rdi
     mov rax, 1
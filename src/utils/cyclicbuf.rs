@@ -1,4 +1,4 @@
-use core::mem::MaybeUninit;
+use core::{mem::MaybeUninit, ptr};
 
 
 pub struct CyclicBuf<T, const CAPACITY: usize> {
@@ -10,7 +10,7 @@ pub struct CyclicBuf<T, const CAPACITY: usize> {
 impl<T, const CAPACITY: usize> Default for CyclicBuf<T, CAPACITY> {
     #[inline]
     fn default() -> Self {
-        Self { buf: MaybeUninit::uninit_array(), begin: 0, end: 0 }
+        Self { buf: [const { MaybeUninit::uninit() }; CAPACITY], begin: 0, end: 0 }
     }
 }
 
@@ -46,17 +46,99 @@ impl<T, const CAPACITY: usize> CyclicBuf<T, CAPACITY> {
     #[inline]
     pub fn peek(&self, output: &mut [T]) -> usize
     where
-        MaybeUninit<T>: Clone
+        T: Copy
     {
-        let mut fb = self.begin;
-        let len = output.len().min(self.len());
-        for i in 0..len {
-            output[i] = unsafe { self.buf[fb].clone().assume_init() };
-            fb = (fb + 1) % CAPACITY;
-        }
+        let (head, tail) = self.as_slices();
+        let len = output.len().min(head.len() + tail.len());
+        let from_head = len.min(head.len());
+        output[..from_head].copy_from_slice(&head[..from_head]);
+        output[from_head..len].copy_from_slice(&tail[..len - from_head]);
         len
     }
 
+    /// Stored elements in order as two contiguous parts (second is empty if data does not wrap)
+    #[inline]
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let (head, tail) = if self.end >= self.begin {
+            (&self.buf[self.begin..self.end], &self.buf[..0])
+        } else {
+            (&self.buf[self.begin..], &self.buf[..self.end])
+        };
+        // elements between begin and end are initialized
+        unsafe {
+            (
+                &*(head as *const [MaybeUninit<T>] as *const [T]),
+                &*(tail as *const [MaybeUninit<T>] as *const [T])
+            )
+        }
+    }
+
+    /// Largest contiguous free region right after the last element.
+    /// Fill it and call `commit` to append elements without copying them one by one
+    #[inline]
+    pub fn spare_slice(&mut self) -> &mut [MaybeUninit<T>] {
+        let spare_end = if self.end >= self.begin {
+            // one slot must stay free to tell full buffer from empty one
+            if self.begin == 0 { CAPACITY - 1 } else { CAPACITY }
+        } else {
+            self.begin - 1
+        };
+        &mut self.buf[self.end..spare_end]
+    }
+
+    /// Mark first `count` elements of `spare_slice` as pushed
+    ///
+    /// # Safety
+    /// `count` must not exceed `spare_slice().len()` and those elements must be initialized
+    #[inline]
+    pub unsafe fn commit(&mut self, count: usize) {
+        debug_assert!(count <= self.spare_slice().len());
+        self.end = (self.end + count) % CAPACITY;
+    }
+
+    /// Drop up to `count` elements from front. Returns number of dropped elements
+    #[inline]
+    pub fn consume(&mut self, count: usize) -> usize
+    where
+        T: Copy
+    {
+        let count = count.min(self.len());
+        self.begin = (self.begin + count) % CAPACITY;
+        count
+    }
+
+    /// Push as many elements of `input` as fit. Returns number of pushed elements
+    #[inline]
+    pub fn extend_from_slice(&mut self, mut input: &[T]) -> usize
+    where
+        T: Copy
+    {
+        let mut pushed = 0;
+        // free space wraps at most once
+        for _ in 0..2 {
+            let spare = self.spare_slice();
+            let count = spare.len().min(input.len());
+            if count == 0 { break; }
+            unsafe {
+                ptr::copy_nonoverlapping(input.as_ptr(), spare.as_mut_ptr() as *mut T, count);
+                self.commit(count);
+            }
+            input = &input[count..];
+            pushed += count;
+        }
+        pushed
+    }
+
+    /// Pop up to `output.len()` elements into `output`. Returns number of popped elements
+    #[inline]
+    pub fn drain_into(&mut self, output: &mut [T]) -> usize
+    where
+        T: Copy
+    {
+        let len = self.peek(output);
+        self.consume(len)
+    }
+
     #[inline]
     pub fn is_full(&self) -> bool {
         self.push_ability() == 0
@@ -72,6 +154,12 @@ impl<T, const CAPACITY: usize> CyclicBuf<T, CAPACITY> {
         CAPACITY - self.push_ability() - 1
     }
 
+    /// Max count of elements buffer can hold
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        CAPACITY - 1
+    }
+
     #[inline]
     pub fn push_ability(&self) -> usize {
 
@@ -205,4 +293,42 @@ mod tests {
         assert_eq!(tmp, [1, 2, 0, 0]);
     }
 
+    #[test]
+    fn extend_drain_test() {
+        let mut buf: CyclicBuf<u8, 8> = CyclicBuf::default();
+
+        assert_eq!(buf.extend_from_slice(&[0, 1, 2, 3, 4]), 5);
+        let mut tmp: [u8; 3] = [0; 3];
+        assert_eq!(buf.drain_into(&mut tmp), 3);
+        assert_eq!(tmp, [0, 1, 2]);
+
+        // wraps around the end of storage
+        assert_eq!(buf.extend_from_slice(&[5, 6, 7, 8, 9, 10]), 5);
+        assert_eq!(buf.is_full(), true);
+        assert_eq!(buf.as_slices(), (&[3, 4, 5, 6, 7][..], &[8, 9][..]));
+
+        let mut tmp: [u8; 8] = [0; 8];
+        assert_eq!(buf.drain_into(&mut tmp), 7);
+        assert_eq!(tmp, [3, 4, 5, 6, 7, 8, 9, 0]);
+        assert_eq!(buf.is_empty(), true);
+    }
+
+    #[test]
+    fn spare_slice_test() {
+        let mut buf: CyclicBuf<u8, 4> = CyclicBuf::default();
+        assert_eq!(buf.spare_slice().len(), 3);
+
+        buf.spare_slice()[0].write(1);
+        buf.spare_slice()[1].write(2);
+        unsafe { buf.commit(2) };
+        assert_eq!(buf.len(), 2);
+        assert_eq!(buf.consume(2), 2);
+
+        // free space is split: [2, 3] at the end and [0] at the start
+        assert_eq!(buf.spare_slice().len(), 2);
+        assert_eq!(buf.extend_from_slice(&[3, 4, 5, 6]), 3);
+        assert_eq!(buf.spare_slice().len(), 0);
+        assert_eq!(buf.as_slices(), (&[3, 4][..], &[5][..]));
+        assert_eq!(buf.consume(10), 3);
+    }
 }
@@ -1,25 +1,51 @@
 use core::{
-    mem::size_of,
-    slice
+    iter,
+    mem::{size_of, size_of_val},
+    ops::RangeInclusive,
+    slice,
+    time::Duration
 };
 
 use crate::{
     socket::{
-        Socket,
+        Addr,
+        MAX_IOVECS,
         WriteError,
         ReadError, self, FlagsChangeError
     },
     bufsocket::BufSocket,
-    event::{Event, self}
+    compress::Compression,
+    delta::{self, DeltaHistory, Layout},
+    event::{Event, self},
+    pixel::{self, Pixel, Rgb888},
+    shm::ShmBuffer,
+    time::Point
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
     GS = 0,
     ARGB = 1,
     VGA = 2,
+    RGB565 = 3,
+    RGB888 = 4,
+    RGBA = 5,
+    /// RGBA with premultiplied alpha
+    PRGBA = 6,
+    /// 1 bit per pixel packed into bytes
+    MONO = 7,
 }
 
-const PROTO_VERSION: u8 = 0;
+/// Oldest protocol version this client can speak
+const MIN_PROTO_VERSION: u8 = 1;
+/// Newest protocol version this client can speak
+const MAX_PROTO_VERSION: u8 = 3;
+/// First protocol version with compression field in present header
+const COMPRESSION_PROTO_VERSION: u8 = 2;
+/// First protocol version accepting delta frames
+const DELTA_PROTO_VERSION: u8 = 3;
+/// Value of compression field marking payload as `delta::encode` output against previous frame
+const DELTA_COMPRESSION: u8 = 3;
 
 pub type PackageType = u8;
 
@@ -30,29 +56,229 @@ mod in_package_types {
 
 mod out_package_types {
     pub const PRESENT: super::PackageType = 0;
+    pub const HELLO: super::PackageType = 1;
+    pub const SHM_ATTACH: super::PackageType = 2;
+    pub const SHM_PRESENT: super::PackageType = 3;
+    pub const PRESENT_REGION: super::PackageType = 4;
+    pub const SET_PALETTE: super::PackageType = 5;
+}
+
+/// Big endian package header assembled on stack so it can be sent together with payload
+struct Header<const N: usize> {
+    buf: [u8; N],
+    len: usize
+}
+
+impl<const N: usize> Header<N> {
+    /// Starts with package size placeholder followed by common fields of all out packages
+    fn new(proto_version: u8, client_id: u8, package_type: PackageType) -> Self {
+        let mut header = Self { buf: [0; N], len: size_of::<u32>() };
+        header
+            .push(proto_version)
+            .push(client_id)
+            .push(package_type);
+        header
+    }
+
+    fn push<T: Sized>(&mut self, mut val: T) -> &mut Self {
+        let sl = unsafe {
+            slice::from_raw_parts_mut((&mut val) as *mut T as *mut u8, size_of::<T>())
+        };
+        #[cfg(target_endian = "little")]
+        sl.reverse();
+        self.buf[self.len..self.len + sl.len()].copy_from_slice(sl);
+        self.len += sl.len();
+        self
+    }
+
+    /// Fill package size for `payload_len` bytes following the header and return header bytes
+    fn finish(&mut self, payload_len: usize) -> &[u8] {
+        let package_size = (self.len - size_of::<u32>() + payload_len) as u32;
+        self.buf[..size_of::<u32>()].copy_from_slice(&package_size.to_be_bytes());
+        self.bytes()
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// Max count of rectangles in one `present_region`
+pub const MAX_REGION_RECTS: usize = 16;
+
+/// Fits region header with `MAX_REGION_RECTS` rectangles
+const MAX_HEADER_LEN: usize = 16 + 8 * MAX_REGION_RECTS;
+
+/// Payload of `len` zero bytes
+fn zeros(len: usize) -> impl Iterator<Item = &'static [u8]> {
+    static ZEROS: [u8; 4096] = [0; 4096];
+    (0..len.div_ceil(ZEROS.len())).map(move |i| &ZEROS[..(len - i * ZEROS.len()).min(ZEROS.len())])
+}
+
+/// Package which was sent only partially because socket would block
+struct PendingPackage {
+    header: Header<MAX_HEADER_LEN>,
+    payload_len: usize,
+    /// Bytes of header and payload already sent
+    written: usize
+}
+
+impl PendingPackage {
+    fn new(mut header: Header<MAX_HEADER_LEN>, payload_len: usize) -> Self {
+        header.finish(payload_len);
+        Self { header, payload_len, written: 0 }
+    }
+
+    #[inline]
+    fn package_type(&self) -> PackageType {
+        self.header.buf[size_of::<u32>() + 2]
+    }
+
+    /// Present or region, whose padding shows on server
+    #[inline]
+    fn is_frame(&self) -> bool {
+        self.package_type() == out_package_types::PRESENT || self.package_type() == out_package_types::PRESENT_REGION
+    }
+
+    #[inline]
+    fn is_done(&self) -> bool {
+        self.written >= self.header.len + self.payload_len
+    }
+
+    /// Write rest of package taking payload from consecutive `parts` (must add up to `payload_len`).
+    /// Up to `MAX_IOVECS` slices go to single `writev`
+    fn write<'a, const CAPACITY: usize, const CHUNK_LEN: usize>(
+        &mut self,
+        s: &mut BufSocket<CAPACITY, CHUNK_LEN>,
+        parts: impl Iterator<Item = &'a [u8]>
+    ) -> Result<(), WriteError> {
+        // skip payload sent before
+        let mut skip = self.written.saturating_sub(self.header.len);
+        let mut parts = parts.filter_map(|part| {
+            let sent = skip.min(part.len());
+            skip -= sent;
+            if sent < part.len() { Some(&part[sent..]) } else { None }
+        });
+
+        let mut bufs: [&[u8]; MAX_IOVECS] = [&[]; MAX_IOVECS];
+        let mut count = 0;
+        if self.written < self.header.len {
+            bufs[0] = &self.header.buf[self.written..self.header.len];
+            count = 1;
+        }
+
+        while !self.is_done() {
+            while count < MAX_IOVECS {
+                match parts.next() {
+                    Some(part) => {
+                        bufs[count] = part;
+                        count += 1;
+                    },
+                    None => break,
+                }
+            }
+
+            let mut written = s.write_vectored(&bufs[..count])?;
+            self.written += written;
+
+            let mut done = 0;
+            while done < count && written >= bufs[done].len() {
+                written -= bufs[done].len();
+                done += 1;
+            }
+            if done < count {
+                bufs[done] = &bufs[done][written..];
+            }
+            bufs.copy_within(done..count, 0);
+            count -= done;
+        }
+        Ok(())
+    }
+}
+
+pub type Capabilities = u32;
+
+pub mod capabilities {
+    use super::Capabilities;
+
+    pub const MOUSE_EVENTS: Capabilities = 1 << 0;
+    pub const KEY_EVENTS: Capabilities = 1 << 1;
+    /// Server accepts frames in shared memory (unix socket connections only)
+    pub const SHM_FRAMES: Capabilities = 1 << 2;
+    /// Server accepts partial frames sent with `Client::present_region`
+    pub const REGION_FRAMES: Capabilities = 1 << 3;
+    /// Server accepts custom palette for `Format::VGA` frames sent with `Client::set_palette`
+    pub const PALETTE: Capabilities = 1 << 4;
+
+    /// Everything this version of client understands
+    pub const SUPPORTED: Capabilities = MOUSE_EVENTS | KEY_EVENTS | SHM_FRAMES | REGION_FRAMES | PALETTE;
+}
+
+#[derive(Debug, Clone)]
+pub struct Init {
+    pub client_id: u8,
+    pub proto_version: u8,
+    pub capabilities: Capabilities,
+    /// Initial window size suggested by server (0 if server has no preference)
+    pub w: u16,
+    pub h: u16
+}
+
+impl Init {
+    /// Package type and all fields
+    const PACKAGE_SIZE: usize
+        = size_of::<PackageType>()
+        + size_of::<u8>()  // client id
+        + size_of::<u8>()  // proto version
+        + size_of::<Capabilities>()
+        + size_of::<u16>() // w
+        + size_of::<u16>(); // h
+
+    /// `None` if fields do not fit in `len` bytes left in package
+    fn pull<const CAPACITY: usize, const CHUNK_LEN: usize>(s: &mut BufSocket<CAPACITY, CHUNK_LEN>, len: &mut usize) -> Option<Init> {
+        let mut init = Init { client_id: 0, proto_version: 0, capabilities: 0, w: 0, h: 0 };
+        let complete = s.read_transmuted_limited(&mut init.client_id, len)
+            && s.read_transmuted_limited(&mut init.proto_version, len)
+            && s.read_transmuted_limited(&mut init.capabilities, len)
+            && s.read_transmuted_limited(&mut init.w, len)
+            && s.read_transmuted_limited(&mut init.h, len);
+        complete.then_some(init)
+    }
 }
 
 #[derive(Debug)]
 pub enum Package {
-    Init(u8),
+    Init(Init),
     Event(Event)
 }
 
 #[derive(Debug)]
 pub enum PackageError {
     ReadError(ReadError),
+    /// Server closed connection and all received packages were already read
+    Disconnected,
     UnknownType(u8),
-    EventErr(event::Error)
+    EventErr(event::Error),
+    /// Package is too small for its fields or too big to fit in client buffer
+    BadPackageSize(u32)
 }
 
 impl Package {
-    pub fn pull<const CAPACITY: usize, const CHUNK_LEN: usize>(s: &mut BufSocket<CAPACITY, CHUNK_LEN>) -> Result<Package, PackageError> {
-        let mut pakcage_type: PackageType = 0;
-        assert!(s.read_transmuted(&mut pakcage_type));
-        match pakcage_type {
-            in_package_types::INIT => todo!(),
-            in_package_types::EVENT => match Event::pull(s) {
+    /// Pull package of `len` bytes without size field, `len` is decreased by count of consumed bytes
+    pub fn pull<const CAPACITY: usize, const CHUNK_LEN: usize>(s: &mut BufSocket<CAPACITY, CHUNK_LEN>, len: &mut usize) -> Result<Package, PackageError> {
+        let package_size = *len as u32;
+        let mut package_type: PackageType = 0;
+        if !s.read_transmuted_limited(&mut package_type, len) {
+            return Err(PackageError::BadPackageSize(package_size))
+        }
+        match package_type {
+            in_package_types::INIT => Init::pull(s, len)
+                .map(Package::Init)
+                .ok_or(PackageError::BadPackageSize(package_size)),
+            in_package_types::EVENT => match Event::pull(s, len) {
                 Ok(event) => Ok(Package::Event(event)),
+                Err(event::Error::TooShort) => Err(PackageError::BadPackageSize(package_size)),
                 Err(err) => Err(PackageError::EventErr(err)),
             },
             p => Err(PackageError::UnknownType(p))
@@ -63,25 +289,160 @@ impl Package {
 #[derive(Debug)]
 pub enum ConnectError {
     ConnectError(socket::ConnectError),
-    ReadError(ReadError)
+    WriteError(WriteError),
+    ReadError(ReadError),
+    /// Init package size is too small to hold init or too big to fit in client buffer
+    BadPackageSize(u32),
+    /// Server sent something other than init first
+    UnexpectedPackageType(PackageType),
+    /// Server closed connection in the middle of init package
+    ShortRead { expected: usize, got: usize },
+    /// Server closed connection before sending init
+    Eof,
+    /// Init was not received in time
+    Timeout,
+    /// Server does not support any protocol version from `Client::supported_proto_versions()`
+    VersionMismatch { server: u8 }
+}
+
+#[derive(Debug)]
+pub enum ShmError {
+    /// Server did not enable `capabilities::SHM_FRAMES`
+    Unsupported,
+    /// `present_shm` called before `attach_shm`
+    NotAttached,
+    /// Damaged rectangle is outside of attached buffer
+    BadDamage,
+    WriteError(WriteError)
 }
 
+/// Count of entries in palette of `Format::VGA` frames
+pub const PALETTE_LEN: usize = 256;
+
+#[derive(Debug)]
+pub enum PaletteError {
+    /// Server did not enable `capabilities::PALETTE`
+    Unsupported,
+    /// No colors or colors do not fit in `PALETTE_LEN` entries
+    OutOfRange,
+    WriteError(WriteError)
+}
+
+/// Rectangle of surface in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub w: u16,
+    pub h: u16
+}
+
+impl Rect {
+    #[inline]
+    pub const fn new(x: u16, y: u16, w: u16, h: u16) -> Self {
+        Self { x, y, w, h }
+    }
+
+    /// True if rectangle lies within `w` x `h` surface
+    #[inline]
+    pub fn fits(&self, w: u16, h: u16) -> bool {
+        self.x as u32 + self.w as u32 <= w as u32 && self.y as u32 + self.h as u32 <= h as u32
+    }
+
+    /// Widen horizontally to multiples of `step` pixels, not past surface width `w`
+    fn aligned(&self, step: u16, w: u16) -> Rect {
+        let x = self.x - self.x % step;
+        let right = ((self.x as u32 + self.w as u32).next_multiple_of(step as u32)).min(w as u32) as u16;
+        Rect { x, y: self.y, w: right - x, h: self.h }
+    }
+}
+
+#[derive(Debug)]
+pub enum RegionError {
+    /// More than `MAX_REGION_RECTS` rectangles
+    TooManyRects,
+    /// Rectangle is outside of surface
+    OutOfBounds(Rect),
+    /// Surface does not have exactly `w * h` pixels
+    SizeMismatch,
+    WriteError(WriteError)
+}
+
+#[derive(Debug)]
+pub enum PresentError {
+    /// Count of pixels is not `w * h`
+    SizeMismatch,
+    WriteError(WriteError)
+}
+
+fn check_size<P: Pixel>(w: u16, h: u16, pixels: &[P]) -> Result<(), PresentError> {
+    if pixels.len() == pixel::surface_len::<P>(w, h) { Ok(()) } else { Err(PresentError::SizeMismatch) }
+}
+
+/// How long `Client::connect` waits for init package
+pub const DEFAULT_INIT_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct Client<const CAPACITY: usize, const CHUNK_LEN: usize> {
     s: BufSocket<CAPACITY, CHUNK_LEN>,
-    id: u8
+    init: Init,
+    pending: Option<PendingPackage>,
+    /// Size of attached shared memory buffer
+    shm: Option<(u16, u16)>,
+    /// Server shows frame stored in `DeltaHistory` by last `present_delta`
+    delta_base: bool,
+    /// Server may show zero padding or pixels of older frame, so `present_region` sends whole surface
+    frame_damaged: bool,
+    /// Colors of last `set_palette`, pending palette is finished with them
+    palette: [Rgb888; PALETTE_LEN]
 }
 
 impl<const CAPACITY: usize, const CHUNK_LEN: usize> Client<CAPACITY, CHUNK_LEN> {
-    pub fn connect(ip: [u8; 4], port: u16) -> Result<Self, ConnectError> {
-        match BufSocket::connect(ip, port) {
-            Ok(mut s) => match Self::wait_for_init(&mut s.soc()) {
-                Ok(id) => Ok(Self { s, id,  }),
-                Err(err) => Err(ConnectError::ReadError(err)),
-            },
+    pub fn connect(addr: Addr) -> Result<Self, ConnectError> {
+        Self::connect_timeout(addr, DEFAULT_INIT_TIMEOUT)
+    }
+
+    /// Connect and wait at most `init_timeout` for server to answer handshake
+    pub fn connect_timeout(addr: Addr, init_timeout: Duration) -> Result<Self, ConnectError> {
+        match BufSocket::connect(addr) {
+            Ok(mut s) => Self::handshake(&mut s, init_timeout).map(|init| Self { s, init, pending: None, shm: None, delta_base: false, frame_damaged: false, palette: [Rgb888::default(); PALETTE_LEN] }),
             Err(err) => Err(ConnectError::ConnectError(err)),
         }
     }
 
+    /// Connect to server listening on unix domain socket at `path`
+    #[inline]
+    pub fn connect_unix(path: &str) -> Result<Self, ConnectError> {
+        Self::connect(Addr::Unix(path))
+    }
+
+    #[inline]
+    pub fn connect_unix_timeout(path: &str, init_timeout: Duration) -> Result<Self, ConnectError> {
+        Self::connect_timeout(Addr::Unix(path), init_timeout)
+    }
+
+    fn handshake(s: &mut BufSocket<CAPACITY, CHUNK_LEN>, init_timeout: Duration) -> Result<Init, ConnectError> {
+        Self::send_hello(s, capabilities::SUPPORTED)
+            .map_err(ConnectError::WriteError)?;
+
+        let init = Self::wait_for_init(s, init_timeout)?;
+
+        if Self::supported_proto_versions().contains(&init.proto_version) {
+            Ok(init)
+        } else {
+            Err(ConnectError::VersionMismatch { server: init.proto_version })
+        }
+    }
+
+    fn send_hello(s: &mut BufSocket<CAPACITY, CHUNK_LEN>, capabilities: Capabilities) -> Result<(), WriteError> {
+        // id is not assigned yet
+        let mut header = Header::<MAX_HEADER_LEN>::new(MAX_PROTO_VERSION, 0, out_package_types::HELLO);
+        header
+            .push(MIN_PROTO_VERSION)
+            .push(MAX_PROTO_VERSION)
+            .push(capabilities);
+        s.write_all(header.finish(0))
+    }
+
     #[inline]
     pub fn set_non_blocking_mode(&mut self, nbm: bool) -> Result<(), FlagsChangeError> {
         self.s.set_non_blocking_mode(nbm)
@@ -89,75 +450,435 @@ impl<const CAPACITY: usize, const CHUNK_LEN: usize> Client<CAPACITY, CHUNK_LEN>
 
     #[inline]
     pub fn id(&self) -> u8 {
-        self.id
+        self.init.client_id
+    }
+
+    /// Last init received from server
+    #[inline]
+    pub fn init(&self) -> &Init {
+        &self.init
+    }
+
+    /// Capabilities enabled by server for this client
+    #[inline]
+    pub fn capabilities(&self) -> Capabilities {
+        self.init.capabilities
     }
 
-    pub fn proto_version() -> u8 {
-        PROTO_VERSION
+    /// Protocol version negotiated with server
+    #[inline]
+    pub fn proto_version(&self) -> u8 {
+        self.init.proto_version
+    }
+
+    pub fn supported_proto_versions() -> RangeInclusive<u8> {
+        MIN_PROTO_VERSION..=MAX_PROTO_VERSION
     }
 
-    fn wait_for_init(s: &mut Socket) -> Result<u8, ReadError> {
+    fn wait_for_init(s: &mut BufSocket<CAPACITY, CHUNK_LEN>, timeout: Duration) -> Result<Init, ConnectError> {
+        let start = Point::now();
+        loop {
+            if let Some(init) = Self::pull_init(s)? {
+                return Ok(init)
+            }
+
+            let remaining = timeout.checked_sub(start.elapsed().unwrap_or_default())
+                .ok_or(ConnectError::Timeout)?;
+
+            if !s.wait_readable(remaining).map_err(ConnectError::ReadError)? {
+                return Err(ConnectError::Timeout)
+            }
+
+            match s.bufferize_chunk() {
+                Ok(_) => {},
+                Err(ReadError::Eof) => return Err(if s.bytes_available() == 0 {
+                    ConnectError::Eof
+                } else {
+                    ConnectError::ShortRead {
+                        expected: Self::pending_package_len(s),
+                        got: s.bytes_available()
+                    }
+                }),
+                Err(err) => return Err(ConnectError::ReadError(err)),
+            }
+        }
+    }
+
+    /// Pull init if it is fully buffered
+    fn pull_init(s: &mut BufSocket<CAPACITY, CHUNK_LEN>) -> Result<Option<Init>, ConnectError> {
         let mut package_size: u32 = 0;
+        if !s.peek_transmuted(&mut package_size) {
+            return Ok(None)
+        }
+        if package_size == 0 {
+            return Err(ConnectError::BadPackageSize(package_size))
+        }
+
+        // type first, so other packages are reported as such whatever their size
+        let mut head = [0u8; size_of::<u32>() + size_of::<PackageType>()];
+        if s.peek(&mut head) < head.len() {
+            return Ok(None)
+        }
+        let package_type: PackageType = head[size_of::<u32>()];
+        if package_type != in_package_types::INIT {
+            return Err(ConnectError::UnexpectedPackageType(package_type))
+        }
 
-        let mut btr: usize = 0;
-        while btr < size_of::<u32>() {
-            btr = s.read_transmuted(&mut package_size)?;
+        if (package_size as usize) < Init::PACKAGE_SIZE
+            || size_of::<u32>() + package_size as usize > s.capacity() {
+            return Err(ConnectError::BadPackageSize(package_size))
         }
-        assert_eq!(btr, size_of::<u32>());
-        assert_eq!(package_size as usize, size_of::<u8>() + size_of::<u8>());
 
-        let mut package_type: u8 = 0;
-        let mut client_id: u8 = 0;
-        assert_eq!(s.read_transmuted(&mut package_type)?, size_of::<u8>());
-        assert_eq!(s.read_transmuted(&mut client_id)?, size_of::<u8>());
+        if s.bytes_available() < Self::pending_package_len(s) {
+            return Ok(None)
+        }
 
-        assert_eq!(package_type, 0);
-        Ok(client_id)
+        let mut len = package_size as usize - size_of::<PackageType>();
+        s.discard(head.len());
+        let init = Init::pull(s, &mut len).ok_or(ConnectError::BadPackageSize(package_size))?;
+        // skip trailing fields unknown to this version of client
+        s.discard(len);
+        Ok(Some(init))
     }
 
-    pub fn read_package(&mut self) -> Result<Option<Package>, PackageError> {
-        self.s.bufferize()
-            .map_err(|e| PackageError::ReadError(e))?;
+    /// Size of package at the front of buffer including size field
+    fn pending_package_len(s: &mut BufSocket<CAPACITY, CHUNK_LEN>) -> usize {
+        let mut package_size: u32 = 0;
+        if s.peek_transmuted(&mut package_size) {
+            size_of::<u32>() + package_size as usize
+        } else {
+            size_of::<u32>()
+        }
+    }
 
+    fn pull_package(s: &mut BufSocket<CAPACITY, CHUNK_LEN>) -> Result<Option<Package>, PackageError> {
         let mut package_size: u32 = 0;
-        if self.s.peek_transmuted(&mut package_size) {
-            if self.s.bytes_available() >= size_of::<u32>() + package_size as usize {
-                self.s.read_transmuted(&mut package_size);
-                return Package::pull(&mut self.s).map(|p| Some(p));
-            }
-        }
-        Ok(None)
-    }
-
-    pub fn present<P: Sized>(&mut self, format: Format, w: u16, h: u16, pixels: &[P]) -> Result<(), WriteError> {
-        let format = format as u8;
-        let pixel_size = size_of::<P>() as u8;
-
-        let package_size
-            =(size_of::<u8>()  // Self::proto_version()
-            + size_of::<u8>()  // client id
-            + size_of::<u8>()  // package type
-            + size_of::<u8>()  // format
-            + size_of::<u8>()  // pixel_size
-            + size_of::<u16>() // w
-            + size_of::<u16>() // h
-            + size_of::<P>() * pixels.len()
-        ) as u32;
-
-        self.s.write_transmuted(package_size)?;
-        self.s.write_transmuted(Self::proto_version())?;
-        self.s.write_transmuted(self.id)?;
-        self.s.write_transmuted(out_package_types::PRESENT)?;
-        self.s.write_transmuted(format)?;
-        self.s.write_transmuted(pixel_size)?;
-        self.s.write_transmuted(w)?;
-        self.s.write_transmuted(h)?;
-        self.s.write_bytes(unsafe {
-            slice::from_raw_parts(pixels.as_ptr() as *const u8, pixels.len() * size_of::<P>())
-        })?;
+        if !s.peek_transmuted(&mut package_size) {
+            return Ok(None)
+        }
+        // would never be buffered completely
+        if size_of::<u32>() + package_size as usize > s.capacity() {
+            return Err(PackageError::BadPackageSize(package_size))
+        }
+        if s.bytes_available() < size_of::<u32>() + package_size as usize {
+            return Ok(None)
+        }
+
+        s.discard(size_of::<u32>());
+        let mut len = package_size as usize;
+        let package = Package::pull(s, &mut len);
+        // skip trailing fields unknown to this version of client and rest of malformed package
+        s.discard(len);
+        package.map(Some)
+    }
+
+    /// Receive next package if it is fully available. Init packages also update client state.
+    /// Packages received before server closed connection are still delivered, then `PackageError::Disconnected` is returned
+    pub fn read_package(&mut self) -> Result<Option<Package>, PackageError> {
+        match self.s.bufferize() {
+            Ok(_) | Err(ReadError::Eof) => {},
+            Err(err) => return Err(PackageError::ReadError(err)),
+        }
+
+        let package = Self::pull_package(&mut self.s)?;
+        if package.is_none() && self.s.is_eof() {
+            return Err(PackageError::Disconnected)
+        }
+        if let Some(Package::Init(init)) = &package {
+            self.init = init.clone();
+            self.delta_base = false;
+        }
+        Ok(package)
+    }
+
+    /// Send frame. Header and pixels are written with single `writev` (more only on short writes).
+    ///
+    /// In non blocking mode `WriteError::WouldBlock` means frame was either dropped or,
+    /// if `has_pending_frame()`, sent partially. Next `present` resumes pending frame first:
+    /// with new pixels if frame layout is the same (then that frame counts as presented),
+    /// otherwise by padding it with zeros. Either way next `present_region` sends whole surface
+    pub fn present<P: Pixel>(&mut self, w: u16, h: u16, pixels: &[P]) -> Result<(), PresentError> {
+        check_size(w, h, pixels)?;
+        self.present_payload::<P>(w, h, pixel::as_bytes(pixels), Compression::None as u8)
+            .map_err(PresentError::WriteError)
+    }
+
+    /// Same as `present` but pixels are compressed into `scratch` first.
+    /// Raw pixels are sent instead if server speaks protocol without compression,
+    /// or compressed frame does not fit `scratch` or is not smaller than raw one.
+    ///
+    /// Compressed frame interrupted in non blocking mode is never resumed with new pixels,
+    /// it is padded with zeros and dropped by server
+    pub fn present_compressed<P: Pixel>(
+        &mut self,
+        w: u16,
+        h: u16,
+        pixels: &[P],
+        compression: Compression,
+        scratch: &mut [u8]
+    ) -> Result<(), PresentError> {
+        check_size(w, h, pixels)?;
+        let pixels = pixel::as_bytes(pixels);
+        let compressed_len = if self.proto_version() >= COMPRESSION_PROTO_VERSION && compression != Compression::None {
+            compression.encode(pixels, scratch).filter(|len| *len < pixels.len())
+        } else {
+            None
+        };
+        match compressed_len {
+            Some(len) => self.present_payload::<P>(w, h, &scratch[..len], compression as u8),
+            None => self.present_payload::<P>(w, h, pixels, Compression::None as u8),
+        }.map_err(PresentError::WriteError)
+    }
+
+    /// Send only bytes changed since frame stored in `history`, encoded into `scratch`.
+    /// Keyframe compressed with `Compression::for_format` is sent instead if server does not accept delta frames,
+    /// `history` holds no frame of the same layout, server may show something else
+    /// (other present happened, pending frame, reinit) or delta is not smaller than raw pixels.
+    /// Completely sent frame is stored in `history`.
+    ///
+    /// Non blocking behavior is the same as in `present_compressed`, dropped frame keeps `history` valid
+    pub fn present_delta<P: Pixel>(
+        &mut self,
+        w: u16,
+        h: u16,
+        pixels: &[P],
+        history: &mut DeltaHistory,
+        scratch: &mut [u8]
+    ) -> Result<(), PresentError> {
+        check_size(w, h, pixels)?;
+        let layout = Layout { format: P::FORMAT, pixel_size: P::SIZE, w, h };
+        let bytes = pixel::as_bytes(pixels);
+        let delta_len = match history.base(layout) {
+            Some(prev) if self.delta_base && self.proto_version() >= DELTA_PROTO_VERSION && prev.len() == bytes.len() =>
+                delta::encode(prev, bytes, scratch).filter(|len| *len < bytes.len()),
+            _ => None,
+        };
+        match delta_len {
+            Some(len) => self.present_payload::<P>(w, h, &scratch[..len], DELTA_COMPRESSION).map_err(PresentError::WriteError),
+            None => self.present_compressed(w, h, pixels, Compression::for_format(P::FORMAT), scratch),
+        }?;
+        history.store(layout, bytes);
+        self.delta_base = true;
         Ok(())
     }
 
+    fn present_payload<P: Pixel>(&mut self, w: u16, h: u16, payload: &[u8], compression: u8) -> Result<(), WriteError> {
+        let mut header = Header::new(self.proto_version(), self.id(), out_package_types::PRESENT);
+        header
+            .push(P::FORMAT as u8)
+            .push(P::SIZE)
+            .push(w)
+            .push(h);
+        if self.proto_version() >= COMPRESSION_PROTO_VERSION {
+            header.push(compression);
+        }
+        let frame = PendingPackage::new(header, payload.len());
+
+        let (frame, resumed) = match self.pending.take() {
+            // rest of compressed payload depends on the part already sent
+            Some(pending) if compression == Compression::None as u8 && pending.header.bytes() == frame.header.bytes() =>
+                (pending, true),
+            pending => {
+                self.pending = pending;
+                self.finish_pending()?;
+                (frame, false)
+            },
+        };
+        self.send(frame, iter::once(payload))?;
+        // part sent by earlier call may hold older pixels
+        self.frame_damaged = resumed;
+        Ok(())
+    }
+
+    /// Share `buf` with server. Only one buffer is attached at a time, attaching replaces previous one.
+    /// Server keeps its own mapping, so `buf` may be dropped any time (next `present_shm` shows stale pixels then)
+    pub fn attach_shm<P: Pixel>(&mut self, buf: &ShmBuffer<P>) -> Result<(), ShmError> {
+        if self.capabilities() & capabilities::SHM_FRAMES == 0 {
+            return Err(ShmError::Unsupported)
+        }
+        self.finish_pending().map_err(ShmError::WriteError)?;
+
+        let mut header = Header::new(self.proto_version(), self.id(), out_package_types::SHM_ATTACH);
+        header
+            .push(P::FORMAT as u8)
+            .push(P::SIZE)
+            .push(buf.w())
+            .push(buf.h());
+        let mut package = PendingPackage::new(header, 0);
+        package.written = self.s.write_vectored_with_fd(&[package.header.bytes()], buf.fd())
+            .map_err(ShmError::WriteError)?;
+        // descriptor is delivered, rest of package follows as usual
+        let result = self.send(package, iter::empty()).map_err(ShmError::WriteError);
+        if result.is_ok() {
+            self.shm = Some((buf.w(), buf.h()));
+        } else if self.has_pending_frame() {
+            // server switches buffers only when pending attach is finished
+            self.shm = None;
+        }
+        result
+    }
+
+    /// Tell server that rectangle `x, y, w, h` of attached buffer changed
+    pub fn present_shm(&mut self, x: u16, y: u16, w: u16, h: u16) -> Result<(), ShmError> {
+        let (buf_w, buf_h) = self.shm.ok_or(ShmError::NotAttached)?;
+        if x as u32 + w as u32 > buf_w as u32 || y as u32 + h as u32 > buf_h as u32 {
+            return Err(ShmError::BadDamage)
+        }
+        self.finish_pending().map_err(ShmError::WriteError)?;
+
+        let mut header = Header::new(self.proto_version(), self.id(), out_package_types::SHM_PRESENT);
+        header
+            .push(x)
+            .push(y)
+            .push(w)
+            .push(h);
+        self.send(PendingPackage::new(header, 0), iter::empty()).map_err(ShmError::WriteError)
+    }
+
+    /// Complete partially sent package, frame is padded with zeros, palette gets its own colors
+    fn finish_pending(&mut self) -> Result<(), WriteError> {
+        match self.pending.take() {
+            Some(pending) if pending.package_type() == out_package_types::SET_PALETTE => {
+                let palette = self.palette;
+                let count = pending.payload_len / size_of::<Rgb888>();
+                self.send_palette(pending, &palette[..count])
+            },
+            Some(pending) => {
+                let len = pending.payload_len;
+                // zeros stay on server until whole surface is sent
+                self.frame_damaged |= pending.is_frame();
+                self.send(pending, zeros(len))
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Send only `rects` of `w` x `h` surface, server keeps the rest of previous frame.
+    /// If server does not support partial frames whole surface is sent with `present`.
+    ///
+    /// Rectangles should not overlap, overlapping pixels are sent twice. Rectangles of packed formats (`Pixel::PER_ELEMENT > 1`)
+    /// are widened to whole elements.
+    ///
+    /// Interrupted in non blocking mode region is resumed by next `present_region` with the same rectangles,
+    /// other calls pad it with zeros. Partial frames can not repair that, so after padded or resumed frame
+    /// next `present_region` sends whole surface like `present`
+    pub fn present_region<P: Pixel>(&mut self, w: u16, h: u16, pixels: &[P], rects: &[Rect]) -> Result<(), RegionError> {
+        if check_size(w, h, pixels).is_err() {
+            return Err(RegionError::SizeMismatch)
+        }
+        if self.capabilities() & capabilities::REGION_FRAMES == 0 {
+            return self.present_payload::<P>(w, h, pixel::as_bytes(pixels), Compression::None as u8)
+                .map_err(RegionError::WriteError)
+        }
+        if rects.len() > MAX_REGION_RECTS {
+            return Err(RegionError::TooManyRects)
+        }
+        if let Some(rect) = rects.iter().find(|rect| !rect.fits(w, h)) {
+            return Err(RegionError::OutOfBounds(*rect))
+        }
+
+        // packed pixels are sent in whole elements
+        let mut aligned = [Rect::new(0, 0, 0, 0); MAX_REGION_RECTS];
+        for (dst, rect) in aligned.iter_mut().zip(rects) {
+            *dst = rect.aligned(P::PER_ELEMENT, w);
+        }
+        let rects = &aligned[..rects.len()];
+
+        let mut header = Header::new(self.proto_version(), self.id(), out_package_types::PRESENT_REGION);
+        header
+            .push(P::FORMAT as u8)
+            .push(P::SIZE)
+            .push(w)
+            .push(h)
+            .push(rects.len() as u8);
+        for rect in rects {
+            header
+                .push(rect.x)
+                .push(rect.y)
+                .push(rect.w)
+                .push(rect.h);
+        }
+        let payload_len = rects.iter()
+            .map(|rect| pixel::surface_len::<P>(rect.w, rect.h) * size_of::<P>())
+            .sum();
+        let frame = PendingPackage::new(header, payload_len);
+
+        // rows of each rectangle top to bottom
+        let row_len = pixel::row_len::<P>(w);
+        let rows = rects.iter().flat_map(|rect| (rect.y..rect.y + rect.h).map(move |y| {
+            let start = y as usize * row_len + (rect.x / P::PER_ELEMENT) as usize;
+            pixel::as_bytes(&pixels[start..start + pixel::row_len::<P>(rect.w)])
+        }));
+
+        if let Some(pending) = self.pending.take() {
+            if pending.header.bytes() == frame.header.bytes() {
+                self.send(pending, rows).map_err(RegionError::WriteError)?;
+                // rows sent by earlier call may hold older pixels
+                self.frame_damaged = true;
+                return Ok(())
+            }
+            self.pending = Some(pending);
+        }
+        // pending frame is resumed or padded there, after that whole surface is needed anyway
+        if self.frame_damaged || self.pending.as_ref().is_some_and(PendingPackage::is_frame) {
+            return self.present_payload::<P>(w, h, pixel::as_bytes(pixels), Compression::None as u8)
+                .map_err(RegionError::WriteError)
+        }
+        self.finish_pending().map_err(RegionError::WriteError)?;
+        self.send(frame, rows).map_err(RegionError::WriteError)
+    }
+
+    /// Replace palette entries from `first` on, entries not set keep their previous colors (VGA palette initially).
+    /// Server recolors current frame too, so palette animation needs no new frames.
+    ///
+    /// Interrupted in non blocking mode palette is finished with its own colors before next package
+    pub fn set_palette(&mut self, first: u8, colors: &[Rgb888]) -> Result<(), PaletteError> {
+        if self.capabilities() & capabilities::PALETTE == 0 {
+            return Err(PaletteError::Unsupported)
+        }
+        if colors.is_empty() || first as usize + colors.len() > PALETTE_LEN {
+            return Err(PaletteError::OutOfRange)
+        }
+
+        let mut header = Header::new(self.proto_version(), self.id(), out_package_types::SET_PALETTE);
+        header
+            .push(first)
+            .push(colors.len() as u16);
+        let package = PendingPackage::new(header, size_of_val(colors));
+
+        self.finish_pending().map_err(PaletteError::WriteError)?;
+        self.palette[..colors.len()].copy_from_slice(colors);
+        self.send_palette(package, colors).map_err(PaletteError::WriteError)
+    }
+
+    fn send_palette(&mut self, package: PendingPackage, colors: &[Rgb888]) -> Result<(), WriteError> {
+        // frame pixels stay the same
+        let delta_base = self.delta_base;
+        let result = self.send(package, iter::once(pixel::as_bytes(colors)));
+        self.delta_base = delta_base;
+        result
+    }
+
+    /// True if last frame was not sent completely. It is finished by next `present`
+    #[inline]
+    pub fn has_pending_frame(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    fn send<'a>(&mut self, mut package: PendingPackage, payload: impl Iterator<Item = &'a [u8]>) -> Result<(), WriteError> {
+        let result = package.write(&mut self.s, payload);
+        // anything reaching server may change what it shows
+        if result.is_ok() || package.written > 0 {
+            self.delta_base = false;
+        }
+        result.inspect_err(|_| {
+            // nothing sent yet, so stream is still in sync without it
+            if package.written > 0 {
+                self.pending = Some(package);
+            }
+        })
+    }
+
     //pub fn pull(&mut self) -> Result<Pull, ReadError> {
     //    let mut p: Pull = Pull { flags: 0, w: 0, h: 0 };
     //    self.s.read_bytes(unsafe {
@@ -166,3 +887,721 @@ impl<const CAPACITY: usize, const CHUNK_LEN: usize> Client<CAPACITY, CHUNK_LEN>
     //    Ok(p)
     //}
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{
+        env,
+        fs::{self, File},
+        format,
+        io::{Read, Write},
+        net::TcpListener,
+        os::{fd::{AsRawFd, FromRawFd}, unix::net::UnixListener},
+        process,
+        thread,
+        vec::Vec
+    };
+    use core::time::Duration;
+
+    use crate::{socket::{self, Addr, LOCALHOST, LOCALHOST_V6}, event::Event, shm::ShmBuffer, sys::io_sys, compress::Compression, pixel::{Argb8888, Gray8, Mono, Rgb565, Rgb888, Vga8}};
+    use super::{Client, ConnectError, Package, PackageError, PaletteError, PresentError, ShmError, Rect, RegionError, DeltaHistory};
+
+    extern "C" {
+        fn recvmsg(fd: i32, msg: *mut io_sys::MsgHdr, flags: i32) -> isize;
+    }
+
+    fn gray(bytes: &[u8]) -> Vec<Gray8> {
+        bytes.iter().copied().map(Gray8).collect()
+    }
+
+    const HELLO_LEN: usize = 4 + 1 + 1 + 1 + 1 + 1 + 4;
+
+    /// Accept one client, read its hello and answer with `reply` split in `chunks`
+    fn serve(reply: Vec<u8>, chunks: usize) -> u16 {
+        serve_on("127.0.0.1:0", reply, chunks)
+    }
+
+    fn serve_on(bind_addr: &str, reply: Vec<u8>, chunks: usize) -> u16 {
+        let listener = TcpListener::bind(bind_addr).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut hello = [0; HELLO_LEN];
+            stream.read_exact(&mut hello).unwrap();
+            for chunk in reply.chunks(reply.len().div_ceil(chunks).max(1)) {
+                stream.write_all(chunk).unwrap();
+                stream.flush().unwrap();
+                thread::sleep(Duration::from_millis(10));
+            }
+            thread::sleep(Duration::from_millis(200));
+        });
+        port
+    }
+
+    /// Init of protocol `version` enabling `capabilities`
+    fn init_with(version: u8, capabilities: u8) -> Vec<u8> {
+        let mut init = init_package(11, 0);
+        init[6] = version;
+        init[10] = capabilities;
+        init
+    }
+
+    fn init_package(size: u32, package_type: u8) -> Vec<u8> {
+        let mut p = Vec::new();
+        p.extend_from_slice(&size.to_be_bytes());
+        p.push(package_type);
+        p.push(7); // client id
+        p.push(1); // proto version
+        p.extend_from_slice(&3u32.to_be_bytes());
+        p.extend_from_slice(&640u16.to_be_bytes());
+        p.extend_from_slice(&480u16.to_be_bytes());
+        p
+    }
+
+    #[test]
+    fn fragmented_init_test() {
+        let port = serve(init_package(11, 0), 5);
+        let client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        assert_eq!(client.id(), 7);
+        assert_eq!(client.proto_version(), 1);
+        assert_eq!(client.capabilities(), 3);
+        assert_eq!((client.init().w, client.init().h), (640, 480));
+    }
+
+    #[test]
+    fn bad_size_test() {
+        let port = serve(init_package(3, 0), 1);
+        let r: Result<Client<64, 16>, _> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2));
+        assert!(matches!(r, Err(ConnectError::BadPackageSize(3))));
+    }
+
+    #[test]
+    fn unexpected_type_test() {
+        let port = serve(init_package(11, 1), 1);
+        let r: Result<Client<64, 16>, _> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2));
+        assert!(matches!(r, Err(ConnectError::UnexpectedPackageType(1))));
+
+        // short event package is still reported by its type, even when it arrives byte by byte
+        let mut reply = init_package(3, 1);
+        reply.truncate(7);
+        let port = serve(reply, 7);
+        let r: Result<Client<64, 16>, _> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2));
+        assert!(matches!(r, Err(ConnectError::UnexpectedPackageType(1))));
+    }
+
+    #[test]
+    fn short_read_test() {
+        let mut reply = init_package(11, 0);
+        reply.truncate(8);
+        let port = serve(reply, 1);
+        let r: Result<Client<64, 16>, _> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2));
+        assert!(matches!(r, Err(ConnectError::ShortRead { expected: 15, got: 8 })));
+    }
+
+    #[test]
+    fn eof_test() {
+        let port = serve(Vec::new(), 1);
+        let r: Result<Client<64, 16>, _> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2));
+        assert!(matches!(r, Err(ConnectError::Eof)));
+    }
+
+    #[test]
+    fn timeout_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let r: Result<Client<64, 16>, _> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_millis(50));
+        assert!(matches!(r, Err(ConnectError::Timeout)));
+    }
+
+    #[test]
+    fn v6_test() {
+        let port = serve_on("[::1]:0", init_package(11, 0), 1);
+        let client: Client<64, 16> = Client::connect_timeout(Addr::v6(LOCALHOST_V6, port), Duration::from_secs(2)).unwrap();
+        assert_eq!(client.id(), 7);
+        assert_eq!((client.init().w, client.init().h), (640, 480));
+    }
+
+    #[test]
+    fn unix_test() {
+        let path = env::temp_dir().join(format!("mini-winclient-test-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut hello = [0; HELLO_LEN];
+            stream.read_exact(&mut hello).unwrap();
+            stream.write_all(&init_package(11, 0)).unwrap();
+            thread::sleep(Duration::from_millis(200));
+        });
+
+        let client: Client<64, 16> = Client::connect_unix_timeout(path.to_str().unwrap(), Duration::from_secs(2)).unwrap();
+        assert_eq!(client.id(), 7);
+        assert_eq!((client.init().w, client.init().h), (640, 480));
+        fs::remove_file(&path).unwrap();
+
+        let r: Result<Client<64, 16>, _> = Client::connect_unix(path.to_str().unwrap());
+        assert!(matches!(r, Err(ConnectError::ConnectError(socket::ConnectError::NotFound))));
+
+        let long_path = [b'a'; 200];
+        let r: Result<Client<64, 16>, _> = Client::connect_unix(core::str::from_utf8(&long_path).unwrap());
+        assert!(matches!(r, Err(ConnectError::ConnectError(socket::ConnectError::BadPath))));
+    }
+
+    #[test]
+    fn version_mismatch_test() {
+        let mut reply = init_package(11, 0);
+        reply[6] = 200;
+        let port = serve(reply, 1);
+        let r: Result<Client<64, 16>, _> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2));
+        assert!(matches!(r, Err(ConnectError::VersionMismatch { server: 200 })));
+    }
+
+    #[test]
+    fn disconnect_test() {
+        let mut reply = init_package(11, 0);
+        // close event
+        reply.extend_from_slice(&[0, 0, 0, 2, 1, 0]);
+        let port = serve(reply, 1);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+
+        let mut got_close = false;
+        loop {
+            match client.read_package() {
+                Ok(Some(Package::Event(Event::Close))) => got_close = true,
+                Ok(Some(p)) => panic!("unexpected package: {:?}", p),
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(PackageError::Disconnected) => break,
+                Err(err) => panic!("unexpected error: {:?}", err),
+            }
+        }
+        assert!(got_close);
+        assert!(matches!(client.read_package(), Err(PackageError::Disconnected)));
+    }
+
+    #[test]
+    fn malformed_package_test() {
+        let mut reply = init_package(11, 0);
+        // init without size fields
+        reply.extend_from_slice(&[0, 0, 0, 3, 0, 7, 1]);
+        // mouse move without buttons
+        reply.extend_from_slice(&[0, 0, 0, 6, 1, 4, 0, 1, 0, 2]);
+        // key down with text longer than package
+        reply.extend_from_slice(&[0, 0, 0, 11, 1, 5, 0, 0, 0, 1, 0, 0x41, 0, 5, b'a']);
+        // empty package
+        reply.extend_from_slice(&[0, 0, 0, 0]);
+        // close event is still read in sync
+        reply.extend_from_slice(&[0, 0, 0, 2, 1, 0]);
+        let port = serve(reply, 1);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+
+        let mut results = Vec::new();
+        loop {
+            match client.read_package() {
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(PackageError::Disconnected) => break,
+                result => results.push(format!("{:?}", result)),
+            }
+        }
+        assert_eq!(results, [
+            "Err(BadPackageSize(3))",
+            "Err(BadPackageSize(6))",
+            "Err(BadPackageSize(11))",
+            "Err(BadPackageSize(0))",
+            "Ok(Some(Event(Close)))",
+        ]);
+    }
+
+    #[test]
+    fn oversized_package_test() {
+        let mut reply = init_package(11, 0);
+        reply.extend_from_slice(&[0, 0, 1, 0]);
+        let port = serve(reply, 1);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        loop {
+            match client.read_package() {
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                result => break assert!(matches!(result, Err(PackageError::BadPackageSize(256))), "{:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn shm_test() {
+        const ATTACH_LEN: usize = 4 + 3 + 1 + 1 + 2 + 2;
+        const DAMAGE_LEN: usize = 4 + 3 + 2 * 4;
+
+        let path = env::temp_dir().join(format!("mini-winclient-shm-test-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut hello = [0; HELLO_LEN];
+            stream.read_exact(&mut hello).unwrap();
+            let mut init = init_package(11, 0);
+            init[10] |= 4; // shm frames capability
+            stream.write_all(&init).unwrap();
+
+            let mut attach = [0u8; ATTACH_LEN];
+            let iov = io_sys::IoVec { base: attach.as_mut_ptr() as *const _, len: attach.len() };
+            let mut control = [0u64; 3];
+            let mut msg = io_sys::MsgHdr::with_control(&iov, 1, control.as_mut_ptr() as *mut _, 24);
+            assert_eq!(unsafe { recvmsg(stream.as_raw_fd(), &mut msg, 0) }, ATTACH_LEN as isize);
+            let fd = (control[2] & 0xffff_ffff) as i32;
+
+            let mut damage = [0u8; DAMAGE_LEN];
+            stream.read_exact(&mut damage).unwrap();
+
+            let mut pixels = Vec::new();
+            unsafe { File::from_raw_fd(fd) }.read_to_end(&mut pixels).unwrap();
+            (attach, damage, pixels)
+        });
+
+        let mut client: Client<64, 16> = Client::connect_unix_timeout(path.to_str().unwrap(), Duration::from_secs(2)).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(client.present_shm(0, 0, 1, 1), Err(ShmError::NotAttached)));
+
+        let mut buf: ShmBuffer<Argb8888> = ShmBuffer::new(4, 2).unwrap();
+        for (i, p) in buf.pixels_mut().iter_mut().enumerate() {
+            *p = Argb8888(i as u32);
+        }
+        client.attach_shm(&buf).unwrap();
+        assert!(matches!(client.present_shm(2, 1, 3, 1), Err(ShmError::BadDamage)));
+        client.present_shm(1, 0, 3, 2).unwrap();
+
+        let (attach, damage, pixels) = server.join().unwrap();
+        // size, proto version, id, type, format, pixel size, w, h
+        assert_eq!(attach, [0, 0, 0, 9, 1, 7, 2, 1, 4, 0, 4, 0, 2]);
+        // size, proto version, id, type, x, y, w, h
+        assert_eq!(damage, [0, 0, 0, 11, 1, 7, 3, 0, 1, 0, 0, 0, 3, 0, 2]);
+        let expected: Vec<u8> = (0..8u32).flat_map(|i| i.to_ne_bytes()).collect();
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn shm_unsupported_test() {
+        let port = serve(init_package(11, 0), 1);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        let buf: ShmBuffer<Gray8> = ShmBuffer::new(4, 4).unwrap();
+        assert!(matches!(client.attach_shm(&buf), Err(ShmError::Unsupported)));
+    }
+
+    #[test]
+    fn present_test() {
+        const W: usize = 1024;
+        const H: usize = 1024;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut hello = [0; HELLO_LEN];
+            stream.read_exact(&mut hello).unwrap();
+            stream.write_all(&init_package(11, 0)).unwrap();
+
+            let mut package = std::vec![0; 13 + W * H];
+            // read slowly so client hits short writes
+            thread::sleep(Duration::from_millis(50));
+            stream.read_exact(&mut package).unwrap();
+            package
+        });
+
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        let pixels: Vec<u8> = (0..W * H).map(|i| i as u8).collect();
+        assert!(matches!(client.present(W as u16, H as u16 - 1, &gray(&pixels)), Err(PresentError::SizeMismatch)));
+        client.present(W as u16, H as u16, &gray(&pixels)).unwrap();
+
+        let package = server.join().unwrap();
+        // size, proto version, id, type, format, pixel size, w, h
+        assert_eq!(&package[..13], &[0, 0x10, 0, 9, 1, 7, 0, 0, 1, 4, 0, 4, 0]);
+        assert_eq!(&package[13..], &pixels[..]);
+    }
+
+    /// Serve `init` and return first `len` bytes sent after hello
+    fn capture(init: Vec<u8>, len: usize) -> (u16, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut hello = [0; HELLO_LEN];
+            stream.read_exact(&mut hello).unwrap();
+            stream.write_all(&init).unwrap();
+
+            let mut package = std::vec![0; len];
+            stream.read_exact(&mut package).unwrap();
+            package
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn present_region_test() {
+        const HEADER_LEN: usize = 4 + 3 + 1 + 1 + 2 + 2 + 1 + 8 * 2;
+
+        let (port, server) = capture(init_with(1, 8), HEADER_LEN + 2 + 4);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        let pixels: Vec<u8> = (0..12).collect();
+        assert!(matches!(
+            client.present_region(4, 3, &gray(&pixels), &[Rect::new(3, 0, 2, 1)]),
+            Err(RegionError::OutOfBounds(Rect { x: 3, .. }))
+        ));
+        assert!(matches!(
+            client.present_region(4, 4, &gray(&pixels), &[]),
+            Err(RegionError::SizeMismatch)
+        ));
+        client.present_region(4, 3, &gray(&pixels), &[Rect::new(1, 0, 2, 1), Rect::new(0, 2, 4, 1)]).unwrap();
+
+        let package = server.join().unwrap();
+        // size, proto version, id, type, format, pixel size, w, h, rect count
+        assert_eq!(&package[..14], &[0, 0, 0, 32, 1, 7, 4, 0, 1, 0, 4, 0, 3, 2]);
+        // x, y, w, h of each rect
+        assert_eq!(&package[14..HEADER_LEN], &[0, 1, 0, 0, 0, 2, 0, 1, 0, 0, 0, 2, 0, 4, 0, 1]);
+        assert_eq!(&package[HEADER_LEN..], &[1, 2, 8, 9, 10, 11]);
+    }
+
+    #[test]
+    fn present_region_fallback_test() {
+        let (port, server) = capture(init_with(1, 3), 13 + 12);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        let pixels: Vec<u8> = (0..12).collect();
+        client.present_region(4, 3, &gray(&pixels), &[Rect::new(0, 0, 1, 1)]).unwrap();
+
+        let package = server.join().unwrap();
+        // whole surface as regular present
+        assert_eq!(&package[..13], &[0, 0, 0, 21, 1, 7, 0, 0, 1, 0, 4, 0, 3]);
+        assert_eq!(&package[13..], &pixels[..]);
+    }
+
+    #[test]
+    fn present_mono_region_test() {
+        const HEADER_LEN: usize = 4 + 3 + 1 + 1 + 2 + 2 + 1 + 8 * 2;
+
+        let (port, server) = capture(init_with(1, 8), HEADER_LEN + 2);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        // 12 x 2 surface is 2 bytes per row
+        let pixels = [Mono(0x10), Mono(0x20), Mono(0x30), Mono(0x40)];
+        assert!(matches!(client.present_region(12, 3, &pixels, &[]), Err(RegionError::SizeMismatch)));
+        client.present_region(12, 2, &pixels, &[Rect::new(3, 1, 2, 1), Rect::new(9, 0, 3, 1)]).unwrap();
+
+        let package = server.join().unwrap();
+        // size, proto version, id, type, format, pixel size, w, h, rect count
+        assert_eq!(&package[..14], &[0, 0, 0, 28, 1, 7, 4, 7, 1, 0, 12, 0, 2, 2]);
+        // rects widened to whole bytes
+        assert_eq!(&package[14..HEADER_LEN], &[0, 0, 0, 1, 0, 8, 0, 1, 0, 8, 0, 0, 0, 4, 0, 1]);
+        assert_eq!(&package[HEADER_LEN..], &[0x30, 0x20]);
+    }
+
+    #[test]
+    fn present_rgb565_test() {
+        let (port, server) = capture(init_package(11, 0), 13 + 4);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        client.present(2, 1, &[Rgb565::from_u16(0xf800), Rgb565::from_u16(0x001f)]).unwrap();
+
+        let package = server.join().unwrap();
+        assert_eq!(&package[..13], &[0, 0, 0, 13, 1, 7, 0, 3, 2, 0, 2, 0, 1]);
+        // little endian regardless of host
+        assert_eq!(&package[13..], &[0x00, 0xf8, 0x1f, 0x00]);
+    }
+
+    #[test]
+    fn set_palette_test() {
+        let mut pixels: Vec<Vga8> = (0..64).map(|i| Vga8((i * 37) as u8)).collect();
+        let mut scratch = [0; 128];
+        let mut history_buf = [0; 64];
+        let mut history = DeltaHistory::new(&mut history_buf);
+
+        let (port, server) = capture(init_with(3, 16), 14 + 64 + 16 + 14 + 3);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        let colors = [Rgb888 { r: 1, g: 2, b: 3 }, Rgb888 { r: 4, g: 5, b: 6 }];
+        assert!(matches!(client.set_palette(255, &colors), Err(PaletteError::OutOfRange)));
+        assert!(matches!(client.set_palette(0, &[]), Err(PaletteError::OutOfRange)));
+
+        client.present_delta(8, 8, &pixels, &mut history, &mut scratch).unwrap();
+        client.set_palette(1, &colors).unwrap();
+        pixels[5] = Vga8(0);
+        // palette does not touch pixels, so delta still applies
+        client.present_delta(8, 8, &pixels, &mut history, &mut scratch).unwrap();
+
+        let package = server.join().unwrap();
+        // size, proto version, id, type, first, count, colors
+        assert_eq!(&package[78..94], &[0, 0, 0, 12, 3, 7, 5, 1, 0, 2, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(&package[94..], &[0, 0, 0, 13, 3, 7, 0, 2, 1, 0, 8, 0, 8, 3, 5, 1, 0]);
+    }
+
+    #[test]
+    fn pending_palette_test() {
+        const PALETTE_PACKAGE_LEN: usize = 4 + 3 + 1 + 2 + 3 * 3;
+        const FRAME_LEN: usize = 4 + 3 + 1 + 1 + 2 + 2 + 1;
+
+        let (port, server) = capture(init_with(2, 16), PALETTE_PACKAGE_LEN + FRAME_LEN + 1);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        let colors = [Rgb888 { r: 1, g: 2, b: 3 }, Rgb888 { r: 4, g: 5, b: 6 }, Rgb888 { r: 7, g: 8, b: 9 }];
+
+        // palette interrupted in the middle of second color
+        let mut header = super::Header::new(client.proto_version(), client.id(), super::out_package_types::SET_PALETTE);
+        header
+            .push(10u8)
+            .push(colors.len() as u16);
+        let mut package = super::PendingPackage::new(header, size_of_val(&colors));
+        client.s.write_all(package.header.bytes()).unwrap();
+        client.s.write_all(&[1, 2, 3, 4]).unwrap();
+        package.written = package.header.len + 4;
+        client.palette[..colors.len()].copy_from_slice(&colors);
+        client.pending = Some(package);
+
+        client.present(1, 1, &[Gray8(0xaa)]).unwrap();
+        assert!(!client.has_pending_frame());
+
+        let package = server.join().unwrap();
+        // size, proto version, id, type, first, count, colors
+        assert_eq!(&package[..PALETTE_PACKAGE_LEN], &[0, 0, 0, 15, 2, 7, 5, 10, 0, 3, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(&package[PALETTE_PACKAGE_LEN..], &[0, 0, 0, 11, 2, 7, 0, 0, 1, 0, 1, 0, 1, 0, 0xaa]);
+    }
+
+    #[test]
+    fn set_palette_unsupported_test() {
+        let port = serve(init_package(11, 0), 1);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        assert!(matches!(client.set_palette(0, &[Rgb888::default()]), Err(PaletteError::Unsupported)));
+    }
+
+    #[test]
+    fn present_compressed_test() {
+        let pixels: Vec<u8> = (0..1024).map(|i| (i / 100) as u8).collect();
+        let mut scratch = [0; 1024];
+        let compressed_len = Compression::Rle.encode(&pixels, &mut scratch).unwrap();
+
+        let (port, server) = capture(init_with(2, 3), 14 + compressed_len + 14 + 1024);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        assert_eq!(client.proto_version(), 2);
+        client.present_compressed(32, 32, &gray(&pixels), Compression::Rle, &mut scratch).unwrap();
+        // does not fit scratch, sent raw
+        client.present_compressed(32, 32, &gray(&pixels), Compression::Rle, &mut scratch[..8]).unwrap();
+
+        let package = server.join().unwrap();
+        let size = 10 + compressed_len as u32;
+        // size, proto version, id, type, format, pixel size, w, h, compression
+        assert_eq!(&package[..14], &[&size.to_be_bytes()[..], &[2, 7, 0, 0, 1, 0, 32, 0, 32, 1]].concat());
+        let mut decoded = [0; 1024];
+        assert_eq!(Compression::Rle.decode(&package[14..14 + compressed_len], &mut decoded), Some(1024));
+        assert_eq!(&decoded[..], &pixels[..]);
+
+        let raw = &package[14 + compressed_len..];
+        assert_eq!(&raw[..14], &[0, 0, 4, 10, 2, 7, 0, 0, 1, 0, 32, 0, 32, 0]);
+        assert_eq!(&raw[14..], &pixels[..]);
+    }
+
+    #[test]
+    fn present_compressed_fallback_test() {
+        let pixels = [0u8; 64];
+        let mut scratch = [0; 64];
+        let (port, server) = capture(init_with(1, 3), 13 + 64);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        client.present_compressed(8, 8, &gray(&pixels), Compression::Rle, &mut scratch).unwrap();
+
+        // protocol 1 has no compression field
+        let package = server.join().unwrap();
+        assert_eq!(&package[..13], &[0, 0, 0, 73, 1, 7, 0, 0, 1, 0, 8, 0, 8]);
+        assert_eq!(&package[13..], &pixels[..]);
+    }
+
+    #[test]
+    fn present_delta_test() {
+        // no runs, so keyframes are sent raw
+        let mut pixels: Vec<u8> = (0..64).map(|i| (i * 37) as u8).collect();
+        let mut scratch = [0; 128];
+        let mut history_buf = [0; 64];
+        let mut history = DeltaHistory::new(&mut history_buf);
+
+        let (port, server) = capture(init_with(3, 3), 14 + 64 + 14 + 6 + 14 + 64 + 14 + 64);
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        client.present_delta(8, 8, &gray(&pixels), &mut history, &mut scratch).unwrap();
+        pixels[10] = 1;
+        pixels[50] = 2;
+        client.present_delta(8, 8, &gray(&pixels), &mut history, &mut scratch).unwrap();
+        // server frame is replaced, history is stale
+        client.present(8, 8, &gray(&pixels)).unwrap();
+        client.present_delta(8, 8, &gray(&pixels), &mut history, &mut scratch).unwrap();
+
+        let package = server.join().unwrap();
+        // size, proto version, id, type, format, pixel size, w, h, compression
+        assert_eq!(&package[..14], &[0, 0, 0, 74, 3, 7, 0, 0, 1, 0, 8, 0, 8, 0]);
+        let delta = &package[78..98];
+        assert_eq!(&delta[..14], &[0, 0, 0, 16, 3, 7, 0, 0, 1, 0, 8, 0, 8, 3]);
+        // skip, count, bytes
+        assert_eq!(&delta[14..], &[10, 1, 1, 39, 1, 2]);
+        let keyframe = &package[98 + 14 + 64..];
+        assert_eq!(keyframe[13], 0);
+        assert_eq!(&keyframe[14..], &pixels[..]);
+    }
+
+    #[test]
+    fn present_delta_fallback_test() {
+        let pixels = [5u8; 64];
+        let mut scratch = [0; 64];
+        let mut history_buf = [0; 64];
+        let mut history = DeltaHistory::new(&mut history_buf);
+        let (port, server) = capture(init_with(2, 3), 2 * (14 + 2));
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        client.present_delta(8, 8, &gray(&pixels), &mut history, &mut scratch).unwrap();
+        client.present_delta(8, 8, &gray(&pixels), &mut history, &mut scratch).unwrap();
+
+        // protocol 2 gets only keyframes, compressed with codec of the format
+        let package = server.join().unwrap();
+        assert_eq!(&package[..16], &[0, 0, 0, 12, 2, 7, 0, 0, 1, 0, 8, 0, 8, 1, 189, 5]);
+        assert_eq!(&package[..16], &package[16..]);
+    }
+
+    #[test]
+    fn non_blocking_present_test() {
+        const W: usize = 1024;
+        const H: usize = 1024;
+        const FRAMES: usize = 16;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut hello = [0; HELLO_LEN];
+            stream.read_exact(&mut hello).unwrap();
+            stream.write_all(&init_package(11, 0)).unwrap();
+
+            thread::sleep(Duration::from_millis(50));
+            let mut stream_bytes = Vec::new();
+            stream.read_to_end(&mut stream_bytes).unwrap();
+
+            // every package must still be a well formed present (padded ones included)
+            let mut rest = &stream_bytes[..];
+            let mut packages = 0;
+            while !rest.is_empty() {
+                let size = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+                let h = u16::from_be_bytes(rest[11..13].try_into().unwrap()) as usize;
+                assert_eq!(&rest[4..11], &[1, 7, 0, 0, 1, 4, 0]);
+                assert_eq!(size, 9 + W * h);
+                rest = &rest[4 + size..];
+                packages += 1;
+            }
+            packages
+        });
+
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        client.set_non_blocking_mode(true).unwrap();
+
+        let pixels = std::vec![Gray8(0xff); W * H];
+        let mut presented = 0;
+        let mut would_block = 0;
+        while presented < FRAMES {
+            // alternate layouts so pending frames are both resumed and padded
+            let h = if would_block % 2 == 0 { H } else { H / 2 };
+            match client.present(W as u16, h as u16, &pixels[..W * h]) {
+                Ok(()) => presented += 1,
+                Err(PresentError::WriteError(super::WriteError::WouldBlock)) => {
+                    would_block += 1;
+                    thread::sleep(Duration::from_millis(1));
+                },
+                Err(err) => panic!("unexpected error: {:?}", err),
+            }
+        }
+        assert!(!client.has_pending_frame());
+        assert!(would_block > 0);
+        drop(client);
+        assert!(server.join().unwrap() >= FRAMES);
+    }
+
+    #[test]
+    fn non_blocking_present_region_test() {
+        const W: usize = 1024;
+        const H: usize = 1024;
+        const FRAMES: usize = 16;
+        let layouts = [
+            [Rect::new(3, 5, W as u16 - 7, H as u16 - 9), Rect::new(0, 0, 1, 1)],
+            [Rect::new(0, 0, W as u16, H as u16 / 2), Rect::new(10, H as u16 - 1, 5, 1)],
+        ];
+        let pixels: Vec<u8> = (0..W * H).map(|i| (i % 251) as u8).collect();
+        let expected: Vec<Vec<u8>> = layouts.iter().map(|rects| rects.iter().flat_map(|r| {
+            let pixels = &pixels;
+            (r.y..r.y + r.h).flat_map(move |y| {
+                let start = y as usize * W + r.x as usize;
+                pixels[start..start + r.w as usize].iter().copied()
+            })
+        }).collect()).collect();
+
+        let (port, server) = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            (port, thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut hello = [0; HELLO_LEN];
+                stream.read_exact(&mut hello).unwrap();
+                let mut init = init_package(11, 0);
+                init[10] = 8;
+                stream.write_all(&init).unwrap();
+
+                thread::sleep(Duration::from_millis(50));
+                let mut stream_bytes = Vec::new();
+                stream.read_to_end(&mut stream_bytes).unwrap();
+                stream_bytes
+            }))
+        };
+
+        let mut client: Client<64, 16> = Client::connect_timeout(Addr::v4(LOCALHOST, port), Duration::from_secs(2)).unwrap();
+        client.set_non_blocking_mode(true).unwrap();
+
+        let frame = gray(&pixels);
+        let mut presented = 0;
+        let mut would_block = 0;
+        while presented < FRAMES {
+            match client.present_region(W as u16, H as u16, &frame, &layouts[would_block % 2]) {
+                Ok(()) => presented += 1,
+                Err(RegionError::WriteError(super::WriteError::WouldBlock)) => {
+                    would_block += 1;
+                    thread::sleep(Duration::from_millis(1));
+                },
+                Err(err) => panic!("unexpected error: {:?}", err),
+            }
+        }
+        assert!(would_block > 0);
+        drop(client);
+
+        // interrupted payloads are padded with zeros, then whole surface is sent again
+        let stream_bytes = server.join().unwrap();
+        let mut rest = &stream_bytes[..];
+        let mut packages = 0;
+        let mut padded = false;
+        // what server shows, `None` where nothing was sent
+        let mut surface = std::vec![None; W * H];
+        while !rest.is_empty() {
+            let size = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            if rest[6] == 0 {
+                assert_eq!(&rest[4..13], &[1, 7, 0, 0, 1, 4, 0, 4, 0]);
+                let payload = &rest[13..4 + size];
+                assert_eq!(payload.len(), W * H);
+                padded |= payload != &pixels[..];
+                surface.iter_mut().zip(payload).for_each(|(dst, src)| *dst = Some(*src));
+            } else {
+                assert_eq!(&rest[4..14], &[1, 7, 4, 0, 1, 4, 0, 4, 0, 2]);
+                let layout = layouts.iter().position(|rects| {
+                    rects.iter().flat_map(|r| [r.x, r.y, r.w, r.h]).flat_map(u16::to_be_bytes).eq(rest[14..30].iter().copied())
+                }).unwrap();
+                let payload = &rest[30..4 + size];
+                assert_eq!(payload.len(), expected[layout].len());
+                padded |= payload != &expected[layout][..];
+                let mut payload = payload.iter();
+                for r in &layouts[layout] {
+                    for y in r.y as usize..(r.y + r.h) as usize {
+                        for x in r.x as usize..(r.x + r.w) as usize {
+                            surface[y * W + x] = payload.next().copied();
+                        }
+                    }
+                }
+            }
+            rest = &rest[4 + size..];
+            packages += 1;
+        }
+        assert!(packages >= FRAMES);
+        assert!(padded);
+        // no padding is left after last successful present
+        assert!(surface.iter().zip(&pixels).all(|(shown, pixel)| shown.is_none_or(|shown| shown == *pixel)));
+    }
+}
@@ -1,4 +1,4 @@
-use core::{fmt::{Write, self}, ffi::c_void};
+use core::{fmt::{Write, self}, ffi::{c_void, CStr}, ops::{Deref, DerefMut}};
 
 use crate::sys::io_sys;
 
@@ -10,15 +10,61 @@ impl WriteFd {
     pub fn new(fd: io_sys::Fd) -> Self {
         return Self(fd)
     }
+
+    /// Write whole `data`, retrying partial and interrupted writes
+    pub fn write_all(&mut self, mut data: &[u8]) -> Result<(), io_sys::ErrNo> {
+        while !data.is_empty() {
+            match unsafe { io_sys::write(self.0, data.as_ptr() as *const c_void, data.len()) } {
+                Ok(0) => return Err(io_sys::errno::EIO),
+                Ok(n) => data = &data[n..],
+                Err(io_sys::errno::EINTR) => {},
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Write for WriteFd {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        match unsafe {
-            io_sys::write(self.0, s.as_ptr() as *const c_void, s.len())
-        } {
-            Ok(_) => Ok(()),
-            Err(_) => Err(fmt::Error),
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// File opened for writing, closed on drop
+pub struct FileFd(WriteFd);
+
+impl FileFd {
+    /// Create file with `rw-r--r--` permissions or truncate existing one
+    pub fn create(path: &CStr) -> Result<Self, io_sys::ErrNo> {
+        let flags = io_sys::O_WRONLY | io_sys::O_CREAT | io_sys::O_TRUNC | io_sys::O_CLOEXEC;
+        unsafe { io_sys::open(path, flags, 0o644) }.map(|fd| Self(WriteFd(fd)))
+    }
+
+    /// Open existing file, e.g. device or pipe, without truncating it
+    pub fn open(path: &CStr) -> Result<Self, io_sys::ErrNo> {
+        unsafe { io_sys::open(path, io_sys::O_WRONLY | io_sys::O_CLOEXEC, 0) }.map(|fd| Self(WriteFd(fd)))
+    }
+}
+
+impl Deref for FileFd {
+    type Target = WriteFd;
+
+    fn deref(&self) -> &WriteFd {
+        &self.0
+    }
+}
+
+impl DerefMut for FileFd {
+    fn deref_mut(&mut self) -> &mut WriteFd {
+        &mut self.0
+    }
+}
+
+impl Drop for FileFd {
+    fn drop(&mut self) {
+        unsafe {
+            io_sys::close(self.0.0)
         }
     }
 }